@@ -1,10 +1,14 @@
 //! More complex drag and drop example
 //!
-//! Displays a list of filenames when they're dropped on the textview widget.
+//! Displays a list of filenames when they're dropped on the textview widget. Every drop can be
+//! undone with Ctrl+Z and redone with Ctrl+Shift+Z, using the undo manager in `src/undo.rs`.
 
 extern crate gdk;
 extern crate gio;
+#[macro_use]
+extern crate glib;
 extern crate gtk;
+extern crate gtk_rs_examples;
 
 use std::env::args;
 
@@ -13,6 +17,8 @@ use gio::prelude::*;
 use gtk::prelude::*;
 use gtk::{DestDefaults, TargetFlags};
 
+use gtk_rs_examples::undo::UndoManager;
+
 fn build_ui(application: &gtk::Application) {
     let window = gtk::ApplicationWindow::new(application);
     window.set_title("Drag and Drop Example with a TextView");
@@ -37,11 +43,18 @@ fn build_ui(application: &gtk::Application) {
     )];
     text_view.drag_dest_set(DestDefaults::HIGHLIGHT, &targets, DragAction::COPY);
 
+    // Only edits made within a user action are recorded, so each drop is undone as a whole.
+    let undo = UndoManager::new(&text_view);
+    undo.add_actions(&window);
+    // The actions only hold on to the undo manager weakly, so the window keeps it alive.
+    window.connect_destroy(clone!(@strong undo => move |_| undo.clear()));
+
     // Process any `drag-data-received` events received by the textview. These events include
     // the URL list we're looking for.
     text_view.connect_drag_data_received(|w, _, _, _, d, _, _| {
         // Get the text buffer for the TextView and clear it to make it ready to accept new text.
         let buffer = w.get_buffer().unwrap();
+        buffer.begin_user_action();
         buffer.set_text("");
 
         // Since we only accept `text/uri-list`s here, we don't need to check first, we can simply
@@ -58,6 +71,7 @@ fn build_ui(application: &gtk::Application) {
             // order.
             buffer.insert_at_cursor(&bulleted_file_path);
         }
+        buffer.end_user_action();
     });
 
     // Pack widgets vertically.
@@ -80,6 +94,8 @@ fn main() {
     application.connect_activate(|app| {
         build_ui(app);
     });
+    application.set_accels_for_action("win.undo", &["<Primary>Z"]);
+    application.set_accels_for_action("win.redo", &["<Primary><Shift>Z"]);
 
     application.run(&args().collect::<Vec<_>>());
 }
@@ -0,0 +1,307 @@
+//! The bugs shown in the list, and reading them from JSON or CSV files.
+//!
+//! A JSON file holds an array of objects, a CSV file has a header naming its columns followed
+//! by one bug per line. Either way every bug has a number, a severity and a description, and
+//! may say whether it's fixed:
+//!
+//! ```text
+//! [{"fixed": false, "number": 60482, "severity": "Normal", "description": "..."}]
+//!
+//! fixed,number,severity,description
+//! no,60482,Normal,...
+//! ```
+//!
+//! Bugs that can't be read, or have the number of another bug, are left out and reported
+//! along with the others instead of failing the whole file.
+
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+
+use csv;
+use futures::prelude::*;
+use gio;
+use gio::prelude::*;
+use serde::Deserialize;
+use serde_json;
+
+/// The severities a bug can be changed to, from the most to the least severe.
+pub const SEVERITIES: [&str; 7] = [
+    "Blocker",
+    "Critical",
+    "Major",
+    "Normal",
+    "Minor",
+    "Trivial",
+    "Enhancement",
+];
+
+pub struct Bug {
+    pub fixed: bool,
+    pub number: u32,
+    pub severity: String,
+    pub description: String,
+}
+
+/// The bugs read from a file, and what was wrong with the ones that couldn't be.
+#[derive(Default)]
+pub struct Loaded {
+    pub bugs: Vec<Bug>,
+    pub problems: Vec<String>,
+    numbers: HashSet<u32>,
+}
+
+impl Loaded {
+    /// Adds `bug`, which comes from the record described by `record`, or the reason it can't.
+    fn push(&mut self, record: &str, bug: Result<Bug, String>) {
+        match bug {
+            Ok(bug) => {
+                if self.numbers.insert(bug.number) {
+                    self.bugs.push(bug);
+                } else {
+                    self.problems.push(format!(
+                        "{}: There already is a bug numbered {}",
+                        record, bug.number
+                    ));
+                }
+            }
+            Err(err) => self.problems.push(format!("{}: {}", record, err)),
+        }
+    }
+}
+
+/// Reads the bugs in the file at `path` without blocking the main loop.
+pub fn load(path: PathBuf) -> impl Future<Output = Result<Loaded, String>> {
+    gio::File::new_for_path(&path)
+        .load_contents_async_future()
+        .map(move |result| {
+            let (contents, _) = result.map_err(|err| err.to_string())?;
+            let text = String::from_utf8(contents)
+                .map_err(|_| String::from("The file isn't UTF-8 encoded text"))?;
+            parse(&path, &text)
+        })
+}
+
+/// Reads the bugs in `text`, which is taken as JSON or CSV depending on the extension of
+/// `path`, or on what it looks like if that doesn't tell.
+pub fn parse(path: &Path, text: &str) -> Result<Loaded, String> {
+    let extension = path
+        .extension()
+        .and_then(|extension| extension.to_str())
+        .map(str::to_lowercase);
+    let is_json = match extension.as_deref() {
+        Some("json") => true,
+        Some("csv") => false,
+        _ => text
+            .trim_start_matches('\u{feff}')
+            .trim_start()
+            .starts_with('['),
+    };
+
+    if is_json {
+        parse_json(text)
+    } else {
+        parse_csv(text)
+    }
+}
+
+/// A bug as it's written in a JSON file.
+#[derive(Deserialize)]
+struct JsonBug {
+    #[serde(default)]
+    fixed: Option<bool>,
+    number: u32,
+    severity: String,
+    description: String,
+}
+
+pub fn parse_json(text: &str) -> Result<Loaded, String> {
+    let records = serde_json::from_str::<Vec<serde_json::Value>>(text)
+        .map_err(|err| format!("The file doesn't contain an array of bugs: {}", err))?;
+
+    let mut loaded = Loaded::default();
+    for (i, record) in records.into_iter().enumerate() {
+        let bug = serde_json::from_value::<JsonBug>(record)
+            .map_err(|err| err.to_string())
+            .and_then(|read| {
+                bug(
+                    read.fixed.unwrap_or(false),
+                    read.number,
+                    &read.severity,
+                    &read.description,
+                )
+            });
+        loaded.push(&format!("Bug {}", i + 1), bug);
+    }
+    Ok(loaded)
+}
+
+pub fn parse_csv(text: &str) -> Result<Loaded, String> {
+    let mut reader = csv::ReaderBuilder::new().from_reader(text.as_bytes());
+    let header = reader.headers().map_err(|err| err.to_string())?.clone();
+    if header.is_empty() {
+        return Ok(Loaded::default());
+    }
+
+    let column = |name: &str| {
+        header
+            .iter()
+            .position(|field| field.trim().eq_ignore_ascii_case(name))
+    };
+    let required =
+        |name: &str| column(name).ok_or_else(|| format!("The header has no \"{}\" column", name));
+    let fixed = column("fixed");
+    let number = required("number")?;
+    let severity = required("severity")?;
+    let description = required("description")?;
+
+    let mut loaded = Loaded::default();
+    for record in reader.records() {
+        let (line, bug) = match record {
+            Ok(record) => (
+                record.position().map_or(0, |position| line(text, position)),
+                bug_from_fields(
+                    fixed.map_or("", |i| &record[i]),
+                    &record[number],
+                    &record[severity],
+                    &record[description],
+                ),
+            ),
+            Err(err) => (
+                err.position().map_or(0, |position| line(text, position)),
+                Err(csv_problem(&err)),
+            ),
+        };
+        loaded.push(&format!("Line {}", line), bug);
+    }
+    Ok(loaded)
+}
+
+/// The line of `text` the record at `position` starts on.
+///
+/// After a CRLF `csv` puts the start of the next record on the LF and counts one line short.
+/// A record can't start with a line break, so it's skipped.
+fn line(text: &str, position: &csv::Position) -> usize {
+    let mut start = position.byte() as usize;
+    if text[start..].starts_with('\n') {
+        start += 1;
+    }
+    text[..start].matches('\n').count() + 1
+}
+
+/// What's wrong with a line of a CSV file, without the position `csv` adds to it.
+fn csv_problem(err: &csv::Error) -> String {
+    match *err.kind() {
+        csv::ErrorKind::UnequalLengths {
+            expected_len, len, ..
+        } => format!("Expected {} fields, found {}", expected_len, len),
+        csv::ErrorKind::Utf8 { .. } => String::from("Not UTF-8 encoded text"),
+        _ => err.to_string(),
+    }
+}
+
+fn bug_from_fields(
+    fixed: &str,
+    number: &str,
+    severity: &str,
+    description: &str,
+) -> Result<Bug, String> {
+    let fixed = match fixed.trim().to_lowercase().as_str() {
+        "" | "false" | "no" | "0" => false,
+        "true" | "yes" | "1" => true,
+        _ => return Err(String::from("Fixed isn't true or false")),
+    };
+    let number = number
+        .trim()
+        .parse::<u32>()
+        .map_err(|_| String::from("Number isn't a whole number"))?;
+
+    bug(fixed, number, severity, description)
+}
+
+fn bug(fixed: bool, number: u32, severity: &str, description: &str) -> Result<Bug, String> {
+    let severity = severity.trim();
+    if severity.is_empty() {
+        return Err(String::from("No severity"));
+    }
+
+    Ok(Bug {
+        fixed,
+        number,
+        severity: severity.to_string(),
+        description: description.to_string(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn numbers(loaded: &Loaded) -> Vec<u32> {
+        loaded.bugs.iter().map(|bug| bug.number).collect()
+    }
+
+    #[test]
+    fn bad_numbers_are_reported() {
+        let loaded = parse_csv("number,severity,description\nx,Normal,a\n2,Minor,b\n").unwrap();
+        assert_eq!(numbers(&loaded), [2]);
+        assert_eq!(loaded.problems, ["Line 2: Number isn't a whole number"]);
+
+        let text = r#"[{"number": "x", "severity": "Normal", "description": "a"},
+                       {"number": 2, "severity": "Minor", "description": "b"}]"#;
+        let loaded = parse_json(text).unwrap();
+        assert_eq!(numbers(&loaded), [2]);
+        assert_eq!(loaded.problems.len(), 1);
+        assert!(loaded.problems[0].starts_with("Bug 1: "));
+    }
+
+    #[test]
+    fn duplicate_numbers_are_reported() {
+        let text = "number,severity,description\r\n1,Normal,a\r\n1,Minor,b\r\n";
+        let loaded = parse_csv(text).unwrap();
+        assert_eq!(numbers(&loaded), [1]);
+        assert_eq!(loaded.bugs[0].severity, "Normal");
+        assert_eq!(
+            loaded.problems,
+            ["Line 3: There already is a bug numbered 1"]
+        );
+    }
+
+    #[test]
+    fn missing_severities_are_reported() {
+        let text = r#"[{"number": 1, "severity": " ", "description": "a"},
+                       {"number": 2, "description": "b"},
+                       {"number": 3, "severity": "Major", "description": "c"}]"#;
+        let loaded = parse_json(text).unwrap();
+        assert_eq!(numbers(&loaded), [3]);
+        assert_eq!(loaded.problems.len(), 2);
+        assert_eq!(loaded.problems[0], "Bug 1: No severity");
+        assert!(loaded.problems[1].starts_with("Bug 2: missing field `severity`"));
+    }
+
+    #[test]
+    fn lines_with_too_few_fields_are_reported() {
+        let text = "fixed,number,severity,description\r\nno,1,Normal\r\nyes,2,Minor,b\r\n";
+        let loaded = parse_csv(text).unwrap();
+        assert_eq!(numbers(&loaded), [2]);
+        assert!(loaded.bugs[0].fixed);
+        assert_eq!(loaded.problems, ["Line 2: Expected 4 fields, found 3"]);
+    }
+
+    #[test]
+    fn files_without_a_known_extension_are_sniffed() {
+        let json = r#"  [{"number": 1, "severity": "Normal", "description": "a"}]"#;
+        let csv = "number,severity,description\n1,Normal,a\n";
+        for path in &["bugs", "bugs.txt"] {
+            let loaded = parse(Path::new(path), json).unwrap();
+            assert_eq!(numbers(&loaded), [1]);
+            assert!(loaded.problems.is_empty());
+            let loaded = parse(Path::new(path), csv).unwrap();
+            assert_eq!(numbers(&loaded), [1]);
+            assert!(loaded.problems.is_empty());
+        }
+
+        // The extension wins over what the text looks like.
+        assert!(parse(Path::new("BUGS.JSON"), csv).is_err());
+        assert!(parse(Path::new("bugs.csv"), json).is_err());
+    }
+}
@@ -0,0 +1,185 @@
+//! The bar above the list to narrow down which bugs are shown.
+//!
+//! Bugs are shown when their description contains the search text, their severity is one of
+//! the checked ones and they are fixed or not as asked for. Severities that aren't in the list
+//! to check can't be hidden.
+
+use std::rc::Rc;
+
+use gtk;
+use gtk::prelude::*;
+
+use bugs;
+use Columns;
+
+#[derive(Clone, Copy, PartialEq)]
+enum Status {
+    Any,
+    Unfixed,
+    Fixed,
+}
+
+pub struct FilterBar {
+    pub container: gtk::Box,
+    filter: gtk::TreeModelFilter,
+    search_entry: gtk::SearchEntry,
+    severity_label: gtk::Label,
+    severity_buttons: Vec<gtk::CheckButton>,
+    status_buttons: Vec<(Status, gtk::RadioButton)>,
+}
+
+impl FilterBar {
+    /// Creates the bar deciding which rows of `filter` are visible.
+    ///
+    /// The search entry keeps the bar around for as long as it exists.
+    pub fn new(filter: &gtk::TreeModelFilter) -> Rc<Self> {
+        let container = gtk::Box::new(gtk::Orientation::Horizontal, 6);
+
+        let search_entry = gtk::SearchEntry::new();
+        search_entry.set_placeholder_text(Some("Search descriptions"));
+        container.pack_start(&search_entry, true, true, 0);
+
+        let severity_button = gtk::MenuButton::new();
+        let severity_label = gtk::Label::new(None);
+        severity_button.add(&severity_label);
+        container.pack_start(&severity_button, false, false, 0);
+
+        let severity_box = gtk::Box::new(gtk::Orientation::Vertical, 0);
+        severity_box.set_border_width(6);
+        let severity_buttons = bugs::SEVERITIES
+            .iter()
+            .map(|severity| {
+                let button = gtk::CheckButton::with_label(severity);
+                button.set_active(true);
+                severity_box.pack_start(&button, false, false, 0);
+                button
+            })
+            .collect::<Vec<_>>();
+        severity_box.show_all();
+
+        let popover = gtk::Popover::new(Some(&severity_button));
+        popover.add(&severity_box);
+        severity_button.set_popover(Some(&popover));
+
+        // Linked toggle buttons, only one of which can be active
+        let status_box = gtk::Box::new(gtk::Orientation::Horizontal, 0);
+        status_box.get_style_context().add_class("linked");
+        let any_button = gtk::RadioButton::with_label("All");
+        let status_buttons = vec![
+            (Status::Any, any_button.clone()),
+            (
+                Status::Unfixed,
+                gtk::RadioButton::with_label_from_widget(&any_button, "Unfixed"),
+            ),
+            (
+                Status::Fixed,
+                gtk::RadioButton::with_label_from_widget(&any_button, "Fixed"),
+            ),
+        ];
+        for (_, button) in &status_buttons {
+            button.set_mode(false);
+            status_box.pack_start(button, false, false, 0);
+        }
+        container.pack_start(&status_box, false, false, 0);
+
+        let filter_bar = Rc::new(FilterBar {
+            container,
+            filter: filter.clone(),
+            search_entry,
+            severity_label,
+            severity_buttons,
+            status_buttons,
+        });
+
+        filter.set_visible_func(
+            clone!(@weak filter_bar => @default-return true, move |model, iter| {
+                filter_bar.matches(model, iter)
+            }),
+        );
+
+        filter_bar
+            .search_entry
+            .connect_search_changed(clone!(@strong filter_bar => move |_| {
+                filter_bar.filter.refilter();
+            }));
+        for button in &filter_bar.severity_buttons {
+            button.connect_toggled(clone!(@weak filter_bar => move |_| {
+                filter_bar.update_severity_label();
+                filter_bar.filter.refilter();
+            }));
+        }
+        for (_, button) in &filter_bar.status_buttons {
+            button.connect_toggled(clone!(@weak filter_bar => move |button| {
+                // Both the button turned off and the one turned on say so
+                if button.get_active() {
+                    filter_bar.filter.refilter();
+                }
+            }));
+        }
+
+        filter_bar.update_severity_label();
+        filter_bar
+    }
+
+    fn matches(&self, model: &gtk::TreeModel, iter: &gtk::TreeIter) -> bool {
+        let get_string = |column: Columns| {
+            model
+                .get_value(iter, column as i32)
+                .get::<String>()
+                .ok()
+                .and_then(|value| value)
+                .unwrap_or_default()
+        };
+
+        let search = self.search_entry.get_text().to_lowercase();
+        if !get_string(Columns::Description)
+            .to_lowercase()
+            .contains(search.trim())
+        {
+            return false;
+        }
+
+        let severity = get_string(Columns::Severity);
+        let hidden_severity = bugs::SEVERITIES
+            .iter()
+            .zip(&self.severity_buttons)
+            .any(|(&known, button)| known == severity && !button.get_active());
+        if hidden_severity {
+            return false;
+        }
+
+        let fixed = model
+            .get_value(iter, Columns::Fixed as i32)
+            .get_some::<bool>()
+            .unwrap_or(false);
+        match self.status() {
+            Status::Any => true,
+            Status::Unfixed => !fixed,
+            Status::Fixed => fixed,
+        }
+    }
+
+    fn status(&self) -> Status {
+        self.status_buttons
+            .iter()
+            .find(|(_, button)| button.get_active())
+            .map_or(Status::Any, |&(status, _)| status)
+    }
+
+    fn update_severity_label(&self) {
+        let checked = bugs::SEVERITIES
+            .iter()
+            .zip(&self.severity_buttons)
+            .filter(|&(_, button)| button.get_active())
+            .map(|(&severity, _)| severity)
+            .collect::<Vec<_>>();
+
+        let text = match checked.len() {
+            0 => String::from("No severities"),
+            1 => checked[0].to_string(),
+            n if n == bugs::SEVERITIES.len() => String::from("All severities"),
+            n => format!("{} severities", n),
+        };
+        self.severity_label.set_text(&text);
+    }
+}
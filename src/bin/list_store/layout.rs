@@ -0,0 +1,303 @@
+//! Remembers how the columns of the tree view are laid out, so they look the same after a
+//! restart.
+//!
+//! Right-clicking a column header offers to show or hide columns, and the headers can be
+//! dragged around to change their order. Which columns are visible, their order, the widths
+//! they were resized to and what the list is sorted by are kept in a `glib::KeyFile` in the
+//! user's config directory, with a group for every column:
+//!
+//! ```text
+//! [Column number]
+//! position=1
+//! visible=true
+//! width=120
+//!
+//! [Sort]
+//! column=number
+//! descending=false
+//! ```
+
+use std::cell::RefCell;
+use std::fs;
+use std::path::PathBuf;
+use std::rc::Rc;
+
+use glib;
+use gtk;
+use gtk::prelude::*;
+
+use show_message;
+
+// How long to wait after a change before saving, so resizing a column is written out just once
+const SAVE_DELAY_MS: u32 = 500;
+const SORT_GROUP: &str = "Sort";
+
+pub struct Layout {
+    treeview: gtk::TreeView,
+    sort: gtk::TreeModelSort,
+    /// The columns along with the names they are saved under.
+    columns: Vec<(&'static str, gtk::TreeViewColumn)>,
+    /// Offers to show or hide columns, filled again every time it pops up.
+    columns_menu: gtk::Menu,
+    /// Where the layout is saved, unless there's no config directory to save it in.
+    path: Option<PathBuf>,
+    save_source: RefCell<Option<glib::SourceId>>,
+}
+
+impl Layout {
+    /// Takes care of the columns of `treeview`, which are saved under `names` in the order they
+    /// were added. `sort` is the model the tree view sorts with.
+    ///
+    /// The columns are laid out as saved right away, and saved again whenever they change.
+    pub fn new(
+        treeview: &gtk::TreeView,
+        sort: &gtk::TreeModelSort,
+        names: &[&'static str],
+    ) -> Rc<Self> {
+        let columns = names
+            .iter()
+            .cloned()
+            .zip(treeview.get_columns())
+            .collect::<Vec<_>>();
+        for (_, column) in &columns {
+            column.set_reorderable(true);
+            column.set_resizable(true);
+        }
+
+        // One menu for all headers, attached to the tree view so it shows up on the same screen
+        let columns_menu = gtk::Menu::new();
+        columns_menu.set_property_attach_widget(Some(treeview));
+
+        let layout = Rc::new(Layout {
+            treeview: treeview.clone(),
+            sort: sort.clone(),
+            columns,
+            columns_menu,
+            path: glib::get_user_config_dir()
+                .map(|dir| dir.join("gtk-rs-list-store").join("layout.ini")),
+            save_source: RefCell::new(None),
+        });
+
+        // Only watch once loaded, moving the columns into place isn't a change worth saving
+        layout.load();
+        layout.watch();
+        layout
+    }
+
+    /// Lays out the columns as saved, if anything was saved yet.
+    fn load(&self) {
+        let path = match self.path {
+            Some(ref path) if path.exists() => path,
+            _ => return,
+        };
+
+        let key_file = glib::KeyFile::new();
+        if let Err(err) = key_file.load_from_file(path, glib::KeyFileFlags::NONE) {
+            self.show_error(
+                "Couldn't restore the layout of the columns",
+                &format!("{}: {}", path.display(), err),
+            );
+            return;
+        }
+
+        // Columns that weren't saved, like ones added since, keep their place after the others
+        let mut columns = self.columns.iter().enumerate().collect::<Vec<_>>();
+        columns.sort_by_key(|&(i, &(name, _))| {
+            let position = key_file.get_integer(&group(name), "position").ok();
+            (
+                position.map_or(self.columns.len(), |position| position as usize),
+                i,
+            )
+        });
+        let mut previous = None::<&gtk::TreeViewColumn>;
+        for (_, (name, column)) in columns {
+            self.treeview.move_column_after(column, previous);
+            previous = Some(column);
+
+            if let Ok(visible) = key_file.get_boolean(&group(name), "visible") {
+                column.set_visible(visible);
+            }
+            if let Ok(width) = key_file.get_integer(&group(name), "width") {
+                column.set_fixed_width(width);
+            }
+        }
+
+        let sort_column = key_file
+            .get_string(SORT_GROUP, "column")
+            .ok()
+            .and_then(|name| self.column(&name))
+            .map(|column| column.get_sort_column_id())
+            .filter(|&id| id >= 0);
+        if let Some(id) = sort_column {
+            let order = match key_file.get_boolean(SORT_GROUP, "descending") {
+                Ok(true) => gtk::SortType::Descending,
+                _ => gtk::SortType::Ascending,
+            };
+            self.sort
+                .set_sort_column_id(gtk::SortColumn::Index(id as u32), order);
+        }
+    }
+
+    /// Offers to hide columns from the column headers, and saves the layout shortly after it
+    /// changed.
+    fn watch(self: &Rc<Self>) {
+        for (_, column) in &self.columns {
+            if let Some(button) = column.get_button() {
+                button.connect_button_press_event(
+                    clone!(@weak self as layout => @default-return Inhibit(false), move |_, event| {
+                        if event.get_button() != 3 {
+                            return Inhibit(false);
+                        }
+
+                        layout.update_columns_menu();
+                        layout
+                            .columns_menu
+                            .popup_easy(event.get_button(), event.get_time());
+                        Inhibit(true)
+                    }),
+                );
+            }
+
+            column.connect_property_visible_notify(clone!(@weak self as layout => move |_| {
+                layout.queue_save();
+            }));
+            // Only set once a column is resized by hand, its natural width isn't saved
+            column.connect_property_fixed_width_notify(clone!(@weak self as layout => move |_| {
+                layout.queue_save();
+            }));
+        }
+
+        self.treeview
+            .connect_columns_changed(clone!(@weak self as layout => move |_| {
+                layout.queue_save();
+            }));
+        self.sort
+            .connect_sort_column_changed(clone!(@weak self as layout => move |_| {
+                layout.queue_save();
+            }));
+    }
+
+    /// Fills the menu with the columns to show or hide, except for the last one that's visible.
+    fn update_columns_menu(&self) {
+        let n_visible = self
+            .columns
+            .iter()
+            .filter(|(_, column)| column.get_visible())
+            .count();
+
+        let menu = &self.columns_menu;
+        for item in menu.get_children() {
+            menu.remove(&item);
+        }
+        for column in self.treeview.get_columns() {
+            let title = column
+                .get_title()
+                .map_or_else(String::new, |title| title.to_string());
+            let item = gtk::CheckMenuItem::with_label(&title);
+            item.set_active(column.get_visible());
+            item.set_sensitive(!column.get_visible() || n_visible > 1);
+            item.connect_toggled(move |item| column.set_visible(item.get_active()));
+            menu.append(&item);
+        }
+        menu.show_all();
+    }
+
+    fn column(&self, name: &str) -> Option<&gtk::TreeViewColumn> {
+        self.columns
+            .iter()
+            .find(|&&(known, _)| known == name)
+            .map(|(_, column)| column)
+    }
+
+    /// Saves once nothing changed for a moment.
+    fn queue_save(self: &Rc<Self>) {
+        if self.path.is_none() {
+            return;
+        }
+        if let Some(source) = self.save_source.borrow_mut().take() {
+            glib::source_remove(source);
+        }
+
+        let source = glib::timeout_add_local(
+            SAVE_DELAY_MS,
+            clone!(@weak self as layout => @default-return glib::Continue(false), move || {
+                layout.save_source.replace(None);
+                layout.save();
+                glib::Continue(false)
+            }),
+        );
+        self.save_source.replace(Some(source));
+    }
+
+    /// Saves right away if there are changes waiting to be saved, like before quitting.
+    pub fn flush(&self) {
+        if let Some(source) = self.save_source.borrow_mut().take() {
+            glib::source_remove(source);
+            self.save();
+        }
+    }
+
+    fn save(&self) {
+        let path = match self.path {
+            Some(ref path) => path,
+            None => return,
+        };
+        let key_file = glib::KeyFile::new();
+
+        let order = self.treeview.get_columns();
+        for (name, column) in &self.columns {
+            let group = group(name);
+            if let Some(position) = order.iter().position(|other| other == column) {
+                key_file.set_integer(&group, "position", position as i32);
+            }
+            key_file.set_boolean(&group, "visible", column.get_visible());
+            if column.get_fixed_width() >= 0 {
+                key_file.set_integer(&group, "width", column.get_fixed_width());
+            }
+        }
+
+        if let Some((gtk::SortColumn::Index(id), order)) = self.sort.get_sort_column_id() {
+            let sort_column = self
+                .columns
+                .iter()
+                .find(|(_, column)| column.get_sort_column_id() == id as i32);
+            if let Some((name, _)) = sort_column {
+                key_file.set_string(SORT_GROUP, "column", name);
+                key_file.set_boolean(SORT_GROUP, "descending", order == gtk::SortType::Descending);
+            }
+        }
+
+        if let Some(dir) = path.parent() {
+            if let Err(err) = fs::create_dir_all(dir) {
+                self.show_error(
+                    "Couldn't save the layout of the columns",
+                    &format!("{}: {}", dir.display(), err),
+                );
+                return;
+            }
+        }
+        if let Err(err) = key_file.save_to_file(path) {
+            self.show_error(
+                "Couldn't save the layout of the columns",
+                &format!("{}: {}", path.display(), err),
+            );
+        }
+    }
+
+    /// Tells the user in a dialog, or on stderr once the window is gone, like when quitting.
+    fn show_error(&self, message: &str, details: &str) {
+        match self
+            .treeview
+            .get_toplevel()
+            .and_then(|toplevel| toplevel.downcast::<gtk::Window>().ok())
+        {
+            Some(window) => show_message(&window, gtk::MessageType::Error, message, details),
+            None => eprintln!("{}: {}", message, details),
+        }
+    }
+}
+
+/// The group of the key file the settings of the column called `name` are saved in.
+fn group(name: &str) -> String {
+    format!("Column {}", name)
+}
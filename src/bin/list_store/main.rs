@@ -0,0 +1,616 @@
+//! # List Store Sample
+//!
+//! This sample demonstrates how to show the rows of a `gtk::ListStore` in a `gtk::TreeView`.
+//!
+//! The bugs in the list are read from a JSON or CSV file, see the `bugs` module for how they
+//! look. Pass the file on the command line or pick it with the Open button, otherwise the
+//! sample bugs in `bugs.json` are shown. Files are read without blocking the window, and bugs
+//! that can't be read are listed in a dialog once the others are shown.
+//!
+//! All but the spinner and the icon can be edited in place. Severities are picked from a fixed
+//! list, and a bug can't get the number of another one.
+//!
+//! The bar above the list filters the bugs through a `gtk::TreeModelFilter`, which is sorted
+//! by a `gtk::TreeModelSort` in turn. Paths of the tree view are converted back to paths of the
+//! list store before changing it.
+//!
+//! Columns can be hidden from the menu of their headers, reordered and resized. How they are
+//! laid out is saved along with the sort order, see the `layout` module.
+
+extern crate csv;
+extern crate futures;
+extern crate gio;
+#[macro_use]
+extern crate glib;
+extern crate gtk;
+extern crate serde;
+extern crate serde_json;
+
+use futures::future::{self, AbortHandle};
+use futures::prelude::*;
+use gio::prelude::*;
+use gtk::prelude::*;
+
+use std::cell::RefCell;
+use std::env::args;
+use std::path::PathBuf;
+use std::rc::Rc;
+
+use bugs::{Bug, Loaded};
+use filter::FilterBar;
+use layout::Layout;
+
+mod bugs;
+mod filter;
+mod layout;
+
+// What the layout of the columns is saved under, in the order `add_columns` adds them
+const COLUMN_NAMES: [&str; 6] = [
+    "fixed",
+    "number",
+    "severity",
+    "description",
+    "spinning",
+    "icon",
+];
+// How many of the bugs that couldn't be read are listed, the rest are only counted
+const MAX_PROBLEMS: usize = 10;
+
+#[derive(Debug)]
+#[repr(i32)]
+enum Columns {
+    Fixed = 0,
+    Number,
+    Severity,
+    Description,
+    Pulse,
+    Icon,
+    Active,
+    Sensitive,
+}
+
+struct BugList {
+    window: gtk::ApplicationWindow,
+    label: gtk::Label,
+    model: Rc<gtk::ListStore>,
+    /// Where the bugs shown come from, once there are any.
+    source: RefCell<Option<String>>,
+    loading: RefCell<Option<AbortHandle>>,
+}
+
+impl BugList {
+    /// Shows the bugs that come with the example.
+    fn load_sample(&self) {
+        let loaded = bugs::parse_json(include_str!("bugs.json")).expect("Invalid sample bugs");
+        self.show(String::from("the sample file"), loaded);
+    }
+
+    /// Starts reading the bugs in the file at `path`, which replace the ones shown once they
+    /// are read.
+    fn load(self: &Rc<Self>, path: PathBuf) {
+        if let Some(handle) = self.loading.borrow_mut().take() {
+            handle.abort();
+        }
+
+        let name = path.file_name().map_or_else(
+            || path.display().to_string(),
+            |name| name.to_string_lossy().into_owned(),
+        );
+        self.label.set_text(&format!("Loading {}…", name));
+
+        // Dropping the load future also cancels the pending gio operation
+        let (load, handle) = future::abortable(bugs::load(path));
+        *self.loading.borrow_mut() = Some(handle);
+
+        let bug_list = Rc::downgrade(self);
+        glib::MainContext::default().spawn_local(load.map(move |result| {
+            let result = match result {
+                Ok(result) => result,
+                Err(future::Aborted) => return,
+            };
+            let bug_list = match bug_list.upgrade() {
+                Some(bug_list) => bug_list,
+                None => return,
+            };
+            bug_list.loading.borrow_mut().take();
+
+            match result {
+                Ok(loaded) => bug_list.show(name, loaded),
+                Err(err) => {
+                    bug_list.update_label();
+                    show_message(
+                        &bug_list.window,
+                        gtk::MessageType::Error,
+                        &format!("Couldn't open {}", name),
+                        &err,
+                    );
+                }
+            }
+        }));
+    }
+
+    fn show(&self, source: String, loaded: Loaded) {
+        fill_model(&self.model, &loaded.bugs);
+
+        if !loaded.problems.is_empty() {
+            let mut details = loaded.problems[..loaded.problems.len().min(MAX_PROBLEMS)].join("\n");
+            if loaded.problems.len() > MAX_PROBLEMS {
+                details.push_str(&format!(
+                    "\n… and {} more",
+                    loaded.problems.len() - MAX_PROBLEMS
+                ));
+            }
+            show_message(
+                &self.window,
+                gtk::MessageType::Warning,
+                &format!("Some bugs in {} couldn't be read", source),
+                &details,
+            );
+        }
+
+        *self.source.borrow_mut() = Some(source);
+        self.update_label();
+    }
+
+    fn update_label(&self) {
+        let text = match *self.source.borrow() {
+            Some(ref source) => format!("These are the bugs from {}.", source),
+            None => String::from("Open a JSON or CSV file to see its bugs."),
+        };
+        self.label.set_text(&text);
+    }
+
+    fn open(self: &Rc<Self>) {
+        let file_chooser = gtk::FileChooserDialog::new(
+            Some("Open Bug List"),
+            Some(&self.window),
+            gtk::FileChooserAction::Open,
+        );
+        file_chooser.add_buttons(&[
+            ("Open", gtk::ResponseType::Ok),
+            ("Cancel", gtk::ResponseType::Cancel),
+        ]);
+
+        let filter = gtk::FileFilter::new();
+        filter.set_name(Some("JSON and CSV files"));
+        filter.add_mime_type("application/json");
+        filter.add_mime_type("text/csv");
+        filter.add_pattern("*.json");
+        filter.add_pattern("*.csv");
+        file_chooser.add_filter(&filter);
+
+        file_chooser.connect_response(
+            clone!(@weak self as bug_list => move |file_chooser, response| {
+                if response == gtk::ResponseType::Ok {
+                    if let Some(path) = file_chooser.get_filename() {
+                        bug_list.load(path);
+                    }
+                }
+                file_chooser.close();
+            }),
+        );
+
+        file_chooser.show_all();
+    }
+}
+
+fn show_message<W: IsA<gtk::Window>>(
+    parent: &W,
+    message_type: gtk::MessageType,
+    message: &str,
+    details: &str,
+) {
+    let dialog = gtk::MessageDialog::new(
+        Some(parent),
+        gtk::DialogFlags::MODAL | gtk::DialogFlags::DESTROY_WITH_PARENT,
+        message_type,
+        gtk::ButtonsType::Close,
+        message,
+    );
+    dialog.set_property_secondary_text(Some(details));
+    dialog.connect_response(|dialog, _| dialog.close());
+    dialog.show_all();
+}
+
+fn build_ui(application: &gtk::Application) -> Rc<BugList> {
+    let window = gtk::ApplicationWindow::new(application);
+
+    window.set_title("List Store");
+    window.set_border_width(10);
+    window.set_position(gtk::WindowPosition::Center);
+    window.set_default_size(280, 250);
+
+    let vbox = gtk::Box::new(gtk::Orientation::Vertical, 8);
+    window.add(&vbox);
+
+    let hbox = gtk::Box::new(gtk::Orientation::Horizontal, 8);
+    vbox.add(&hbox);
+
+    let label = gtk::Label::new(None);
+    label.set_line_wrap(true);
+    hbox.pack_start(&label, true, true, 0);
+
+    let open_button = gtk::Button::with_label("Open…");
+    open_button.set_valign(gtk::Align::Center);
+    hbox.pack_start(&open_button, false, false, 0);
+
+    let sw = gtk::ScrolledWindow::new(None::<&gtk::Adjustment>, None::<&gtk::Adjustment>);
+    sw.set_shadow_type(gtk::ShadowType::EtchedIn);
+    sw.set_policy(gtk::PolicyType::Never, gtk::PolicyType::Automatic);
+
+    // The list store can't be sorted once it's filtered, so the sorting happens on top
+    let model = Rc::new(create_model());
+    let filter = gtk::TreeModelFilter::new(&*model, None);
+    let sort = gtk::TreeModelSort::new(&filter);
+    let treeview = gtk::TreeView::with_model(&sort);
+    treeview.set_vexpand(true);
+    treeview.set_search_column(Columns::Description as i32);
+
+    let filter_bar = FilterBar::new(&filter);
+    vbox.add(&filter_bar.container);
+    vbox.add(&sw);
+
+    sw.add(&treeview);
+
+    add_columns(&model, &treeview);
+
+    let layout = Layout::new(&treeview, &sort, &COLUMN_NAMES);
+    application.connect_shutdown(move |_| layout.flush());
+
+    window.show_all();
+
+    let model_clone = model.clone();
+    let spinning = RefCell::new(None);
+    glib::timeout_add_local(80, move || {
+        spinner_timeout(&model_clone, &filter, &sort, &spinning)
+    });
+
+    let bug_list = Rc::new(BugList {
+        window,
+        label,
+        model,
+        source: RefCell::new(None),
+        loading: RefCell::new(None),
+    });
+    bug_list.update_label();
+
+    open_button.connect_clicked(clone!(@weak bug_list => move |_| bug_list.open()));
+
+    bug_list
+}
+
+fn create_model() -> gtk::ListStore {
+    let col_types: [glib::Type; 8] = [
+        glib::Type::Bool,
+        glib::Type::U32,
+        glib::Type::String,
+        glib::Type::String,
+        glib::Type::U32,
+        glib::Type::String,
+        glib::Type::Bool,
+        glib::Type::Bool,
+    ];
+
+    gtk::ListStore::new(&col_types)
+}
+
+/// Replaces the contents of `store` with `bugs`.
+fn fill_model(store: &gtk::ListStore, bugs: &[Bug]) {
+    store.clear();
+
+    let col_indices: [u32; 8] = [0, 1, 2, 3, 4, 5, 6, 7];
+
+    for bug in bugs {
+        let sensitive = !bug.fixed;
+
+        let values: [&dyn ToValue; 8] = [
+            &bug.fixed,
+            &bug.number,
+            &bug.severity,
+            &bug.description,
+            &0u32,
+            &severity_icon(&bug.severity),
+            &false,
+            &sensitive,
+        ];
+        store.set(&store.append(), &col_indices, &values);
+    }
+}
+
+/// Severe bugs get an icon, which is dimmed once they are fixed.
+fn severity_icon(severity: &str) -> &'static str {
+    match severity {
+        "Blocker" | "Critical" | "Major" => "battery-caution-charging-symbolic",
+        _ => "",
+    }
+}
+
+/// Finds the row of the list store shown at `path` in `treeview`, which shows the store
+/// filtered and then sorted.
+fn store_path(treeview: &gtk::TreeView, path: &gtk::TreePath) -> Option<gtk::TreePath> {
+    let sort = treeview
+        .get_model()?
+        .downcast::<gtk::TreeModelSort>()
+        .ok()?;
+    let filter = sort.get_model().downcast::<gtk::TreeModelFilter>().ok()?;
+    let path = sort.convert_path_to_child_path(path)?;
+    filter.convert_path_to_child_path(&path)
+}
+
+fn fixed_toggled<W: IsA<gtk::CellRendererToggle>>(
+    model: &gtk::ListStore,
+    _w: &W,
+    path: gtk::TreePath,
+) {
+    // The row may be gone by the time the edit comes in, like after loading another file
+    let iter = match model.get_iter(&path) {
+        Some(iter) => iter,
+        None => return,
+    };
+    let fixed = match model
+        .get_value(&iter, Columns::Fixed as i32)
+        .get_some::<bool>()
+    {
+        Ok(fixed) => !fixed,
+        Err(_) => return,
+    };
+    model.set_value(&iter, Columns::Fixed as u32, &fixed.to_value());
+    model.set_value(&iter, Columns::Sensitive as u32, &(!fixed).to_value());
+}
+
+/// Changes the number of a bug, unless another bug has that number already.
+fn number_edited(
+    model: &gtk::ListStore,
+    treeview: &gtk::TreeView,
+    path: gtk::TreePath,
+    text: &str,
+) {
+    let number = match text.trim().parse::<u32>() {
+        Ok(number) => number,
+        Err(_) => return,
+    };
+
+    let iter = match model.get_iter(&path) {
+        Some(iter) => iter,
+        None => return,
+    };
+    let taken = (0..model.iter_n_children(None))
+        .filter_map(|n| model.iter_nth_child(None, n))
+        .filter(|other| model.get_path(other).as_ref() != Some(&path))
+        .any(|other| {
+            model
+                .get_value(&other, Columns::Number as i32)
+                .get_some::<u32>()
+                == Ok(number)
+        });
+    if taken {
+        if let Some(window) = treeview
+            .get_toplevel()
+            .and_then(|w| w.downcast::<gtk::Window>().ok())
+        {
+            show_message(
+                &window,
+                gtk::MessageType::Error,
+                "Couldn't change the bug number",
+                &format!("There already is a bug numbered {}.", number),
+            );
+        }
+        return;
+    }
+
+    model.set_value(&iter, Columns::Number as u32, &number.to_value());
+}
+
+fn severity_edited(model: &gtk::ListStore, path: gtk::TreePath, severity: &str) {
+    let iter = match model.get_iter(&path) {
+        Some(iter) => iter,
+        None => return,
+    };
+    model.set_value(&iter, Columns::Severity as u32, &severity.to_value());
+    model.set_value(
+        &iter,
+        Columns::Icon as u32,
+        &severity_icon(severity).to_value(),
+    );
+}
+
+fn add_columns(model: &Rc<gtk::ListStore>, treeview: &gtk::TreeView) {
+    // Column for fixed toggles
+    {
+        let renderer = gtk::CellRendererToggle::new();
+        let model_clone = model.clone();
+        renderer.connect_toggled(clone!(@weak treeview => move |w, path| {
+            if let Some(path) = store_path(&treeview, &path) {
+                fixed_toggled(&model_clone, w, path);
+            }
+        }));
+        let column = gtk::TreeViewColumn::new();
+        column.pack_start(&renderer, true);
+        column.set_title("Fixed?");
+        column.add_attribute(&renderer, "active", Columns::Fixed as i32);
+        column.set_sizing(gtk::TreeViewColumnSizing::Fixed);
+        column.set_fixed_width(50);
+        treeview.append_column(&column);
+    }
+
+    // Column for bug numbers
+    {
+        let renderer = gtk::CellRendererSpin::new();
+        let adjustment = gtk::Adjustment::new(0.0, 0.0, f64::from(u32::MAX), 1.0, 10.0, 0.0);
+        renderer.set_property_adjustment(Some(&adjustment));
+        renderer.set_property_editable(true);
+        let model_clone = model.clone();
+        renderer.connect_edited(clone!(@weak treeview => move |_, path, text| {
+            if let Some(path) = store_path(&treeview, &path) {
+                number_edited(&model_clone, &treeview, path, text);
+            }
+        }));
+        let column = gtk::TreeViewColumn::new();
+        column.pack_start(&renderer, true);
+        column.set_title("Bug number");
+        column.add_attribute(&renderer, "text", Columns::Number as i32);
+        column.set_sort_column_id(Columns::Number as i32);
+        treeview.append_column(&column);
+    }
+
+    // Column for severities
+    {
+        let severities = gtk::ListStore::new(&[glib::Type::String]);
+        for severity in &bugs::SEVERITIES {
+            severities.set(&severities.append(), &[0], &[severity]);
+        }
+
+        let renderer = gtk::CellRendererCombo::new();
+        renderer.set_property_model(Some(&severities));
+        renderer.set_property_text_column(0);
+        renderer.set_property_has_entry(false);
+        renderer.set_property_editable(true);
+        let model_clone = model.clone();
+        renderer.connect_edited(clone!(@weak treeview => move |_, path, text| {
+            if let Some(path) = store_path(&treeview, &path) {
+                severity_edited(&model_clone, path, text);
+            }
+        }));
+        let column = gtk::TreeViewColumn::new();
+        column.pack_start(&renderer, true);
+        column.set_title("Severity");
+        column.add_attribute(&renderer, "text", Columns::Severity as i32);
+        column.set_sort_column_id(Columns::Severity as i32);
+        treeview.append_column(&column);
+    }
+
+    // Column for description
+    {
+        let renderer = gtk::CellRendererText::new();
+        renderer.set_property_editable(true);
+        let model_clone = model.clone();
+        renderer.connect_edited(clone!(@weak treeview => move |_, path, text| {
+            let iter = store_path(&treeview, &path).and_then(|path| model_clone.get_iter(&path));
+            if let Some(iter) = iter {
+                model_clone.set_value(&iter, Columns::Description as u32, &text.to_value());
+            }
+        }));
+        let column = gtk::TreeViewColumn::new();
+        column.pack_start(&renderer, true);
+        column.set_title("Description");
+        column.add_attribute(&renderer, "text", Columns::Description as i32);
+        column.set_sort_column_id(Columns::Description as i32);
+        treeview.append_column(&column);
+    }
+
+    // Column for spinner
+    {
+        let renderer = gtk::CellRendererSpinner::new();
+        let column = gtk::TreeViewColumn::new();
+        column.pack_start(&renderer, true);
+        column.set_title("Spinning");
+        column.add_attribute(&renderer, "pulse", Columns::Pulse as i32);
+        column.add_attribute(&renderer, "active", Columns::Active as i32);
+        treeview.append_column(&column);
+    }
+
+    // Column for symbolic icon
+    {
+        let renderer = gtk::CellRendererPixbuf::new();
+        let column = gtk::TreeViewColumn::new();
+        column.pack_start(&renderer, true);
+        column.set_title("Symbolic icon");
+        column.add_attribute(&renderer, "icon-name", Columns::Icon as i32);
+        column.add_attribute(&renderer, "sensitive", Columns::Sensitive as i32);
+        column.set_sort_column_id(Columns::Icon as i32);
+        treeview.append_column(&column);
+    }
+}
+
+/// Spins the first bug that isn't filtered out, stopping the one before if that changed.
+///
+/// The first row shown is the first row of `sort`, which is converted down through `filter`
+/// to the list store, as the other layers only pass on its values.
+fn spinner_timeout(
+    model: &gtk::ListStore,
+    filter: &gtk::TreeModelFilter,
+    sort: &gtk::TreeModelSort,
+    spinning: &RefCell<Option<gtk::TreeRowReference>>,
+) -> Continue {
+    let iter = sort
+        .get_iter_first()
+        .map(|iter| sort.convert_iter_to_child_iter(&iter))
+        .map(|iter| filter.convert_iter_to_child_iter(&iter));
+    let path = iter.as_ref().and_then(|iter| model.get_path(iter));
+
+    let previous = spinning
+        .borrow()
+        .as_ref()
+        .and_then(gtk::TreeRowReference::get_path);
+    if previous != path {
+        if let Some(previous) = previous.and_then(|path| model.get_iter(&path)) {
+            model.set_value(&previous, Columns::Active as i32 as u32, &false.to_value());
+        }
+        *spinning.borrow_mut() = path.and_then(|path| gtk::TreeRowReference::new(model, &path));
+    }
+
+    // Nothing is shown until the bugs are loaded, or if all of them are filtered out
+    let iter = match iter {
+        Some(iter) => iter,
+        None => return Continue(true),
+    };
+    let pulse = model
+        .get_value(&iter, Columns::Pulse as i32)
+        .get_some::<u32>()
+        .unwrap_or_else(|err| {
+            panic!(
+                "ListStore value for {:?} at first visible entry: {}",
+                Columns::Pulse,
+                err
+            )
+        })
+        .wrapping_add(1);
+
+    model.set_value(&iter, Columns::Pulse as i32 as u32, &pulse.to_value());
+    model.set_value(&iter, Columns::Active as i32 as u32, &true.to_value());
+
+    Continue(true)
+}
+
+fn main() {
+    let application = gtk::Application::new(
+        Some("com.github.gtk-rs.examples.list-store"),
+        gio::ApplicationFlags::HANDLES_OPEN,
+    )
+    .expect("Initialization failed...");
+
+    // Built on startup, so that both activate and open find it
+    let bug_list = Rc::new(RefCell::new(None));
+
+    application.connect_startup(clone!(@strong bug_list => move |app| {
+        *bug_list.borrow_mut() = Some(build_ui(app));
+    }));
+
+    // Without a file to open, the sample bugs are shown unless something else is already
+    application.connect_activate(clone!(@strong bug_list => move |_| {
+        if let Some(ref bug_list) = *bug_list.borrow() {
+            if bug_list.source.borrow().is_none() && bug_list.loading.borrow().is_none() {
+                bug_list.load_sample();
+            }
+            bug_list.window.present();
+        }
+    }));
+
+    // Only one bug list is shown at a time, so the last of the files wins
+    application.connect_open(clone!(@strong bug_list => move |_, files, _| {
+        if let Some(ref bug_list) = *bug_list.borrow() {
+            match files.last().and_then(gio::File::get_path) {
+                Some(path) => bug_list.load(path),
+                None => show_message(
+                    &bug_list.window,
+                    gtk::MessageType::Error,
+                    "Couldn't open the bugs",
+                    "Only local files can be opened.",
+                ),
+            }
+            bug_list.window.present();
+        }
+    }));
+
+    application.run(&args().collect::<Vec<_>>());
+}
@@ -0,0 +1,259 @@
+//! The form to edit all values of an item with, as shown by the Edit and Add dialogs.
+//!
+//! Most editors are bound to the properties of the item both ways, so the item changes right
+//! away. Only the name is checked first: it has to be non-empty and no other row may have it
+//! already. Until then the error is shown below the entry and the item keeps its old name.
+
+use std::cell::Cell;
+use std::rc::Rc;
+
+use gio;
+use gio::prelude::*;
+use glib;
+use gtk;
+use gtk::prelude::*;
+
+use row_data::{self, Category, RowData};
+
+pub struct Editor {
+    pub grid: gtk::Grid,
+    pub name_entry: gtk::Entry,
+    name_error: gtk::Label,
+    valid: Cell<bool>,
+}
+
+impl Editor {
+    /// Creates the editors for `item`. The names of the other items in `model` can't be used.
+    ///
+    /// The name entry keeps the editor around for as long as it exists.
+    pub fn new(item: &RowData, model: &gio::ListStore) -> Rc<Self> {
+        let grid = gtk::Grid::new();
+        grid.set_row_spacing(6);
+        grid.set_column_spacing(12);
+
+        let name_entry = gtk::Entry::new();
+        name_entry.set_hexpand(true);
+        item.bind_property("name", &name_entry, "text")
+            .flags(glib::BindingFlags::DEFAULT | glib::BindingFlags::SYNC_CREATE)
+            .build();
+
+        let name_error = gtk::Label::new(None);
+        name_error.set_xalign(0.0);
+        name_error.get_style_context().add_class("error");
+        name_error.set_no_show_all(true);
+
+        let count_button = gtk::SpinButton::with_range(0.0, 100.0, 1.0);
+        item.bind_property("count", &count_button, "value")
+            .flags(
+                glib::BindingFlags::DEFAULT
+                    | glib::BindingFlags::SYNC_CREATE
+                    | glib::BindingFlags::BIDIRECTIONAL,
+            )
+            .build();
+
+        let category_combo = gtk::ComboBoxText::new();
+        for category in &Category::ALL {
+            category_combo.append(Some(category.nick()), category.label());
+        }
+        item.bind_property("category", &category_combo, "active-id")
+            .flags(
+                glib::BindingFlags::DEFAULT
+                    | glib::BindingFlags::SYNC_CREATE
+                    | glib::BindingFlags::BIDIRECTIONAL,
+            )
+            .transform_to(|_, value| {
+                let category = value.get_some::<Category>().ok()?;
+                Some(category.nick().to_value())
+            })
+            .transform_from(|_, value| {
+                let nick = value.get::<String>().ok()??;
+                Some(Category::from_nick(&nick)?.to_value())
+            })
+            .build();
+
+        let flagged_button = gtk::CheckButton::with_label("Flagged");
+        item.bind_property("flagged", &flagged_button, "active")
+            .flags(
+                glib::BindingFlags::DEFAULT
+                    | glib::BindingFlags::SYNC_CREATE
+                    | glib::BindingFlags::BIDIRECTIONAL,
+            )
+            .build();
+
+        let color_button = gtk::ColorButton::new();
+        color_button.set_halign(gtk::Align::Start);
+        item.bind_property("color", &color_button, "rgba")
+            .flags(
+                glib::BindingFlags::DEFAULT
+                    | glib::BindingFlags::SYNC_CREATE
+                    | glib::BindingFlags::BIDIRECTIONAL,
+            )
+            .build();
+
+        let rows: [(&str, gtk::Widget); 6] = [
+            ("Name", name_entry.clone().upcast()),
+            ("Count", count_button.upcast()),
+            ("Date", date_button(item).upcast()),
+            ("Category", category_combo.upcast()),
+            ("Color", color_button.upcast()),
+            ("", flagged_button.upcast()),
+        ];
+        let mut top = 0;
+        for &(title, ref editor) in &rows {
+            let label = gtk::Label::new(Some(title));
+            label.set_xalign(1.0);
+            grid.attach(&label, 0, top, 1, 1);
+            grid.attach(editor, 1, top, 1, 1);
+            top += 1;
+
+            // Errors about the name go right below it
+            if top == 1 {
+                grid.attach(&name_error, 1, top, 1, 1);
+                top += 1;
+            }
+        }
+
+        let editor = Rc::new(Editor {
+            grid,
+            name_entry,
+            name_error,
+            valid: Cell::new(true),
+        });
+
+        editor.name_entry.connect_changed(
+            clone!(@strong editor, @weak item, @weak model => move |entry| {
+                let name = entry.get_text();
+                let result = validate_name(&model, Some(&item), &name);
+                editor.show_error(result.as_ref().err().map(String::as_str));
+                if result.is_ok() {
+                    item.set_property("name", &name.as_str())
+                        .expect("Couldn't set name");
+                }
+            }),
+        );
+        // A new item starts out without a name, which isn't an error just yet
+        editor
+            .valid
+            .set(validate_name(model, Some(item), &item.get_name()).is_ok());
+
+        editor
+    }
+
+    /// Whether the name that was typed in can be used.
+    pub fn is_valid(&self) -> bool {
+        self.valid.get()
+    }
+
+    fn show_error(&self, error: Option<&str>) {
+        self.valid.set(error.is_none());
+
+        let style_context = self.name_entry.get_style_context();
+        match error {
+            Some(error) => {
+                self.name_error.set_text(error);
+                self.name_error.show();
+                style_context.add_class("error");
+            }
+            None => {
+                self.name_error.hide();
+                style_context.remove_class("error");
+            }
+        }
+    }
+}
+
+/// Checks whether `item` may be named `name`, which has to contain more than whitespace and
+/// can't be the name of any other item in `model`.
+pub fn validate_name(
+    model: &gio::ListStore,
+    item: Option<&RowData>,
+    name: &str,
+) -> Result<(), String> {
+    check_name(name, |name| {
+        (0..model.get_n_items())
+            .filter_map(|position| model.get_object(position))
+            .filter_map(|other| other.downcast::<RowData>().ok())
+            .any(|other| Some(&other) != item && other.get_name().trim() == name)
+    })
+}
+
+/// Checks `name` like `validate_name`, with `is_taken` telling whether a name is used already.
+/// It gets the name with leading and trailing whitespace removed.
+pub fn check_name<F: Fn(&str) -> bool>(name: &str, is_taken: F) -> Result<(), String> {
+    let name = name.trim();
+    if name.is_empty() {
+        return Err(String::from("The name can't be empty"));
+    }
+    if is_taken(name) {
+        return Err(format!("There already is a row named \"{}\"", name));
+    }
+
+    Ok(())
+}
+
+/// A button showing the date of `item`, which opens a calendar to pick another one with.
+fn date_button(item: &RowData) -> gtk::MenuButton {
+    let button = gtk::MenuButton::new();
+    let label = gtk::Label::new(None);
+    item.bind_property("date", &label, "label")
+        .flags(glib::BindingFlags::DEFAULT | glib::BindingFlags::SYNC_CREATE)
+        .transform_to(|_, value| {
+            let date = value.get::<glib::DateTime>().ok()?;
+            let text = date.map_or_else(
+                || String::from("No date"),
+                |date| row_data::format_date(&date),
+            );
+            Some(text.to_value())
+        })
+        .build();
+    button.add(&label);
+
+    let calendar = gtk::Calendar::new();
+    let clear_button = gtk::Button::with_label("No date");
+    let set_button = gtk::Button::with_label("Set");
+    set_button.get_style_context().add_class("suggested-action");
+
+    let buttons = gtk::Box::new(gtk::Orientation::Horizontal, 6);
+    buttons.set_homogeneous(true);
+    buttons.pack_start(&clear_button, true, true, 0);
+    buttons.pack_start(&set_button, true, true, 0);
+
+    let container = gtk::Box::new(gtk::Orientation::Vertical, 6);
+    container.set_border_width(6);
+    container.pack_start(&calendar, false, false, 0);
+    container.pack_start(&buttons, false, false, 0);
+    container.show_all();
+
+    let popover = gtk::Popover::new(Some(&button));
+    popover.add(&container);
+    button.set_popover(Some(&popover));
+
+    // Start at the date of the item, or today if there's none
+    popover.connect_show(clone!(@weak item, @weak calendar => move |_| {
+        let date = item
+            .get_date()
+            .unwrap_or_else(glib::DateTime::new_now_local);
+        let (year, month, day) = date.get_ymd();
+        calendar.select_month(month as u32 - 1, year as u32);
+        calendar.select_day(day as u32);
+    }));
+
+    // Picking a day only takes effect once confirmed, as browsing through the months selects
+    // days as well
+    let set_date = clone!(@weak item, @weak calendar, @weak popover => move || {
+        let (year, month, day) = calendar.get_date();
+        let date = row_data::new_date(year as i32, month as i32 + 1, day as i32);
+        item.set_property("date", &date).expect("Couldn't set date");
+        popover.hide();
+    });
+    calendar.connect_day_selected_double_click(clone!(@strong set_date => move |_| set_date()));
+    set_button.connect_clicked(move |_| set_date());
+
+    clear_button.connect_clicked(clone!(@weak item, @weak popover => move |_| {
+        item.set_property("date", &None::<glib::DateTime>)
+            .expect("Couldn't clear date");
+        popover.hide();
+    }));
+
+    button
+}
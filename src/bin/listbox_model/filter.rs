@@ -0,0 +1,225 @@
+//! Searching and sorting the rows shown by the list box.
+//!
+//! Both are done by the list box itself, through `set_filter_func` and `set_sort_func`, so the
+//! model keeps its order. The list box removes rows by their position though, which stops
+//! matching the model as soon as the rows are sorted, so removing items while sorted binds the
+//! model again to get rows that match.
+
+use std::cell::RefCell;
+use std::cmp::Ordering;
+use std::collections::HashMap;
+use std::rc::Rc;
+
+use gio;
+use gio::prelude::*;
+use glib;
+use gtk;
+use gtk::prelude::*;
+
+use row_data::RowData;
+
+type CreateRow = dyn Fn(&glib::Object) -> gtk::Widget;
+
+pub struct FilterBar {
+    pub container: gtk::Box,
+    pub status: gtk::Label,
+    search_entry: gtk::SearchEntry,
+    sort: gtk::ComboBoxText,
+    listbox: gtk::ListBox,
+    model: gio::ListStore,
+    create_row: RefCell<Option<Rc<CreateRow>>>,
+    /// The item each row currently in the list box shows.
+    items: RefCell<HashMap<gtk::ListBoxRow, RowData>>,
+}
+
+impl FilterBar {
+    pub fn new(listbox: &gtk::ListBox, model: &gio::ListStore) -> Rc<Self> {
+        let container = gtk::Box::new(gtk::Orientation::Horizontal, 5);
+
+        let search_entry = gtk::SearchEntry::new();
+        search_entry.set_placeholder_text(Some("Search by name"));
+        container.pack_start(&search_entry, true, true, 0);
+
+        let sort = gtk::ComboBoxText::new();
+        sort.append(Some("none"), "Unsorted");
+        sort.append(Some("name-asc"), "Name (A to Z)");
+        sort.append(Some("name-desc"), "Name (Z to A)");
+        sort.append(Some("count-asc"), "Count (lowest first)");
+        sort.append(Some("count-desc"), "Count (highest first)");
+        sort.set_active_id(Some("none"));
+        container.pack_start(&sort, false, false, 0);
+
+        let status = gtk::Label::new(None);
+        status.set_xalign(0.0);
+        status.get_style_context().add_class("dim-label");
+
+        let filter_bar = Rc::new(FilterBar {
+            container,
+            status,
+            search_entry,
+            sort,
+            listbox: listbox.clone(),
+            model: model.clone(),
+            create_row: RefCell::new(None),
+            items: RefCell::new(HashMap::new()),
+        });
+
+        filter_bar.connect();
+        filter_bar
+    }
+
+    fn connect(self: &Rc<Self>) {
+        self.listbox.set_filter_func(Some(Box::new(
+            clone!(@weak self as filter_bar => @default-return true, move |row| {
+                match filter_bar.get_item(row) {
+                    Some(item) => filter_bar.matches(&item),
+                    None => true,
+                }
+            }),
+        )));
+
+        self.search_entry
+            .connect_search_changed(clone!(@weak self as filter_bar => move |_| {
+                filter_bar.listbox.invalidate_filter();
+                filter_bar.update_status();
+            }));
+
+        self.sort
+            .connect_changed(clone!(@weak self as filter_bar => move |_| {
+                filter_bar.update_sort();
+            }));
+
+        // Connected to run after the list box's own handler, which already dropped a row by then,
+        // and possibly the wrong one while sorted
+        self.model
+            .connect_local(
+                "items-changed",
+                true,
+                clone!(@weak self as filter_bar => @default-return None, move |values| {
+                    let removed = values[2].get_some::<u32>().expect("removed is a u32");
+                    if removed > 0 && filter_bar.is_sorted() {
+                        filter_bar.rebind();
+                    }
+                    filter_bar.update_status();
+                    None
+                }),
+            )
+            .expect("Couldn't connect to items-changed");
+    }
+
+    /// Binds the model to the list box, using `create_row` to create the row for each item.
+    pub fn bind<F: Fn(&glib::Object) -> gtk::Widget + 'static>(self: &Rc<Self>, create_row: F) {
+        self.create_row.replace(Some(Rc::new(create_row)));
+        self.rebind();
+        self.update_status();
+    }
+
+    fn rebind(self: &Rc<Self>) {
+        let create_row = match *self.create_row.borrow() {
+            Some(ref create_row) => create_row.clone(),
+            None => return,
+        };
+
+        self.listbox.bind_model(
+            Some(&self.model),
+            clone!(@weak self as filter_bar => @default-panic, move |item| {
+                let row = create_row(item);
+                filter_bar.add_row(&row, item);
+                row
+            }),
+        );
+    }
+
+    /// Keeps track of which item `row` shows, and sorts and filters it again whenever the item
+    /// changes.
+    fn add_row(self: &Rc<Self>, row: &gtk::Widget, item: &glib::Object) {
+        let row = row
+            .clone()
+            .downcast::<gtk::ListBoxRow>()
+            .expect("Row is a ListBoxRow");
+        let item = item
+            .clone()
+            .downcast::<RowData>()
+            .expect("Row data is of wrong type");
+
+        let handler = item
+            .connect_local(
+                "notify",
+                false,
+                clone!(@weak self as filter_bar, @weak row => @default-return None, move |_| {
+                    row.changed();
+                    filter_bar.update_status();
+                    None
+                }),
+            )
+            .expect("Couldn't connect to notify");
+        let handler = RefCell::new(Some(handler));
+
+        row.connect_destroy(clone!(@weak self as filter_bar, @weak item => move |row| {
+            filter_bar.items.borrow_mut().remove(row);
+            if let Some(handler) = handler.borrow_mut().take() {
+                item.disconnect(handler);
+            }
+        }));
+
+        self.items.borrow_mut().insert(row, item);
+    }
+
+    /// The item shown by `row`.
+    pub fn get_item(&self, row: &gtk::ListBoxRow) -> Option<RowData> {
+        self.items.borrow().get(row).cloned()
+    }
+
+    fn matches(&self, item: &RowData) -> bool {
+        let query = self.search_entry.get_text().to_lowercase();
+        query.is_empty() || item.get_name().to_lowercase().contains(&query)
+    }
+
+    pub fn is_sorted(&self) -> bool {
+        self.sort.get_active_id().as_deref() != Some("none")
+    }
+
+    fn update_sort(self: &Rc<Self>) {
+        let compare: fn(&RowData, &RowData) -> Ordering = match self.sort.get_active_id().as_deref()
+        {
+            Some("name-asc") => |a, b| compare_names(a, b),
+            Some("name-desc") => |a, b| compare_names(b, a),
+            Some("count-asc") => |a, b| a.get_count().cmp(&b.get_count()),
+            Some("count-desc") => |a, b| b.get_count().cmp(&a.get_count()),
+            _ => {
+                // Without a sort function the rows stay in whatever order they were
+                // sorted in last, so start over in the order of the model
+                self.listbox.set_sort_func(None);
+                self.rebind();
+                return;
+            }
+        };
+
+        self.listbox.set_sort_func(Some(Box::new(
+            clone!(@weak self as filter_bar => @default-return 0, move |a, b| {
+                match (filter_bar.get_item(a), filter_bar.get_item(b)) {
+                    (Some(a), Some(b)) => compare(&a, &b) as i32,
+                    _ => 0,
+                }
+            }),
+        )));
+    }
+
+    fn update_status(&self) {
+        let total = self.model.get_n_items();
+        let visible = (0..total)
+            .filter_map(|position| self.model.get_object(position))
+            .filter_map(|item| item.downcast::<RowData>().ok())
+            .filter(|item| self.matches(item))
+            .count();
+
+        self.status
+            .set_text(&format!("Showing {} of {} rows", visible, total));
+    }
+}
+
+fn compare_names(a: &RowData, b: &RowData) -> Ordering {
+    a.get_name()
+        .to_lowercase()
+        .cmp(&b.get_name().to_lowercase())
+}
@@ -0,0 +1,413 @@
+//! Undo and redo for everything that changes the rows.
+//!
+//! Instead of every button and binding telling the history what it's about to do, the history
+//! watches the model and the items in it, much like saving does. Each change to a property of
+//! an item and each time items are inserted into or removed from the model is recorded, along
+//! with what was there before.
+//!
+//! Changes made in one go, like a move being a removal followed by an insertion, are undone
+//! together. So are quick successive changes to the same property, like typing a name.
+
+use std::cell::{Cell, RefCell};
+use std::collections::{HashMap, HashSet};
+use std::rc::Rc;
+use std::time::{Duration, Instant};
+
+use gio;
+use gio::prelude::*;
+use glib;
+use gtk;
+use gtk::prelude::*;
+
+use row_data::RowData;
+
+// Changes to the same property within this long of each other are undone at once
+const MERGE_INTERVAL: Duration = Duration::from_secs(1);
+const MAX_ENTRIES: usize = 100;
+
+enum Change {
+    Property {
+        item: RowData,
+        property: String,
+        old: glib::Value,
+        new: glib::Value,
+    },
+    /// `removed` was replaced by `added` at `position` in the model.
+    Splice {
+        position: u32,
+        removed: Vec<RowData>,
+        added: Vec<RowData>,
+    },
+}
+
+impl Change {
+    /// Adds the items this change can bring back to `items`.
+    fn collect_items(&self, items: &mut HashSet<RowData>) {
+        match *self {
+            Change::Property { ref item, .. } => {
+                items.insert(item.clone());
+            }
+            Change::Splice {
+                ref removed,
+                ref added,
+                ..
+            } => items.extend(removed.iter().chain(added).cloned()),
+        }
+    }
+
+    fn apply(&self, model: &gio::ListStore, undo: bool) {
+        match *self {
+            Change::Property {
+                ref item,
+                ref property,
+                ref old,
+                ref new,
+            } => {
+                let value = if undo { old } else { new };
+                item.set_property(property.as_str(), value)
+                    .expect("Couldn't restore property");
+            }
+            Change::Splice {
+                position,
+                ref removed,
+                ref added,
+            } => {
+                let (current, restored) = if undo {
+                    (added, removed)
+                } else {
+                    (removed, added)
+                };
+                let restored = restored
+                    .iter()
+                    .map(|item| item.clone().upcast::<glib::Object>())
+                    .collect::<Vec<_>>();
+                model.splice(position, current.len() as u32, &restored);
+            }
+        }
+    }
+}
+
+/// An item whose changes are recorded.
+struct Watched {
+    handler: glib::SignalHandlerId,
+    /// The property values of the item, to know what they were before a change.
+    values: HashMap<String, glib::Value>,
+}
+
+pub struct History {
+    model: gio::ListStore,
+    /// What the model contained after the last change, to know which items got removed.
+    items: RefCell<Vec<RowData>>,
+    /// The items in the model, and the ones that left it but can still be brought back.
+    watched: RefCell<HashMap<RowData, Watched>>,
+    undo_stack: RefCell<Vec<Vec<Change>>>,
+    redo_stack: RefCell<Vec<Vec<Change>>>,
+    /// Set while undoing or redoing, so the changes that causes aren't recorded again.
+    applying: Cell<bool>,
+    /// Set until the main loop gets idle again, to record changes made in one go as one entry.
+    grouping: Cell<bool>,
+    last_change: Cell<Option<Instant>>,
+    undo_action: gio::SimpleAction,
+    redo_action: gio::SimpleAction,
+    /// Called whenever a different entry becomes the one to undo next.
+    latest_changed: RefCell<Vec<Box<dyn Fn()>>>,
+}
+
+impl History {
+    /// Records the changes to `model` from now on and adds the "win.undo" and "win.redo"
+    /// actions to `window`, which keeps the history around until it's destroyed.
+    pub fn new(model: &gio::ListStore, window: &gtk::ApplicationWindow) -> Rc<Self> {
+        let undo_action = gio::SimpleAction::new("undo", None);
+        let redo_action = gio::SimpleAction::new("redo", None);
+        window.add_action(&undo_action);
+        window.add_action(&redo_action);
+
+        let history = Rc::new(History {
+            model: model.clone(),
+            items: RefCell::new(Vec::new()),
+            watched: RefCell::new(HashMap::new()),
+            undo_stack: RefCell::new(Vec::new()),
+            redo_stack: RefCell::new(Vec::new()),
+            applying: Cell::new(false),
+            grouping: Cell::new(false),
+            last_change: Cell::new(None),
+            undo_action,
+            redo_action,
+            latest_changed: RefCell::new(Vec::new()),
+        });
+
+        history
+            .items
+            .replace(history.get_items(0, model.get_n_items()));
+        for item in history.items.borrow().iter() {
+            history.watch_item(item);
+        }
+        history.connect();
+        history.update_actions();
+
+        window.connect_destroy(clone!(@strong history => move |_| {
+            history.clear();
+        }));
+        history
+    }
+
+    fn connect(self: &Rc<Self>) {
+        self.undo_action
+            .connect_activate(clone!(@weak self as history => move |_, _| {
+                history.undo();
+            }));
+        self.redo_action
+            .connect_activate(clone!(@weak self as history => move |_, _| {
+                history.redo();
+            }));
+
+        self.model.connect_items_changed(
+            clone!(@weak self as history => move |_, position, removed, added| {
+                let added = history.get_items(position, added);
+                for item in &added {
+                    history.watch_item(item);
+                }
+
+                let start = position as usize;
+                let removed = history
+                    .items
+                    .borrow_mut()
+                    .splice(start..start + removed as usize, added.iter().cloned())
+                    .collect();
+
+                history.record(Change::Splice {
+                    position,
+                    removed,
+                    added,
+                });
+            }),
+        );
+    }
+
+    /// Calls `f` whenever something new is recorded, undone or redone, so the entry that the
+    /// next undo reverts is a different one.
+    pub fn connect_latest_changed<F: Fn() + 'static>(&self, f: F) {
+        self.latest_changed.borrow_mut().push(Box::new(f));
+    }
+
+    fn notify_latest_changed(&self) {
+        for f in self.latest_changed.borrow().iter() {
+            f();
+        }
+    }
+
+    fn get_items(&self, position: u32, n_items: u32) -> Vec<RowData> {
+        (position..position + n_items)
+            .filter_map(|position| self.model.get_object(position))
+            .filter_map(|item| item.downcast::<RowData>().ok())
+            .collect()
+    }
+
+    /// Remembers the property values of `item` and records whenever they change.
+    fn watch_item(self: &Rc<Self>, item: &RowData) {
+        if self.watched.borrow().contains_key(item) {
+            return;
+        }
+
+        let values = item
+            .list_properties()
+            .iter()
+            .map(|pspec| {
+                let property = pspec.get_name();
+                let value = item
+                    .get_property(property.as_str())
+                    .expect("Couldn't get property");
+                (property.to_string(), value)
+            })
+            .collect();
+
+        let handler = item
+            .connect_local(
+                "notify",
+                false,
+                clone!(@weak self as history, @weak item => @default-return None, move |values| {
+                    let pspec = values[1]
+                        .get::<glib::ParamSpec>()
+                        .expect("Notify has a ParamSpec")
+                        .expect("Notify has a ParamSpec");
+                    let property = pspec.get_name();
+                    let new = item
+                        .get_property(property.as_str())
+                        .expect("Couldn't get property");
+                    let old = history
+                        .watched
+                        .borrow_mut()
+                        .get_mut(&item)
+                        .and_then(|watched| {
+                            watched.values.insert(property.to_string(), new.clone())
+                        });
+
+                    if let Some(old) = old {
+                        history.record(Change::Property {
+                            item: item.clone(),
+                            property: property.to_string(),
+                            old,
+                            new,
+                        });
+                    }
+                    None
+                }),
+            )
+            .expect("Couldn't connect to notify");
+        self.watched
+            .borrow_mut()
+            .insert(item.clone(), Watched { handler, values });
+    }
+
+    fn record(self: &Rc<Self>, change: Change) {
+        if self.applying.get() {
+            return;
+        }
+
+        let now = Instant::now();
+        let recent = match self.last_change.replace(Some(now)) {
+            Some(last_change) => now - last_change < MERGE_INTERVAL,
+            None => false,
+        };
+
+        let mut undo_stack = self.undo_stack.borrow_mut();
+        if self.grouping.get() {
+            if let Some(entry) = undo_stack.last_mut() {
+                entry.push(change);
+                return;
+            }
+        }
+
+        self.redo_stack.borrow_mut().clear();
+        let merged = recent && merge(undo_stack.last_mut(), &change);
+        if !merged {
+            undo_stack.push(vec![change]);
+            if undo_stack.len() > MAX_ENTRIES {
+                undo_stack.remove(0);
+            }
+        }
+        drop(undo_stack);
+        self.prune();
+        self.update_actions();
+        if !merged {
+            self.notify_latest_changed();
+        }
+
+        self.grouping.set(true);
+        glib::idle_add_local(
+            clone!(@weak self as history => @default-return glib::Continue(false), move || {
+                history.grouping.set(false);
+                glib::Continue(false)
+            }),
+        );
+    }
+
+    pub fn undo(&self) {
+        let entry = match self.undo_stack.borrow_mut().pop() {
+            Some(entry) => entry,
+            None => return,
+        };
+        self.apply(&entry, true);
+        self.redo_stack.borrow_mut().push(entry);
+    }
+
+    pub fn redo(&self) {
+        let entry = match self.redo_stack.borrow_mut().pop() {
+            Some(entry) => entry,
+            None => return,
+        };
+        self.apply(&entry, false);
+        self.undo_stack.borrow_mut().push(entry);
+    }
+
+    fn apply(&self, entry: &[Change], undo: bool) {
+        self.applying.set(true);
+        if undo {
+            for change in entry.iter().rev() {
+                change.apply(&self.model, true);
+            }
+        } else {
+            for change in entry {
+                change.apply(&self.model, false);
+            }
+        }
+        self.applying.set(false);
+
+        // Whatever comes next is a change of its own
+        self.last_change.set(None);
+        self.update_actions();
+        self.notify_latest_changed();
+    }
+
+    /// Stops watching the items that left the model once no undo or redo step can bring them
+    /// back, so they don't pile up.
+    fn prune(&self) {
+        let mut referenced = self.items.borrow().iter().cloned().collect::<HashSet<_>>();
+        for entry in self
+            .undo_stack
+            .borrow()
+            .iter()
+            .chain(self.redo_stack.borrow().iter())
+        {
+            for change in entry {
+                change.collect_items(&mut referenced);
+            }
+        }
+
+        let mut watched = self.watched.borrow_mut();
+        let gone = watched
+            .keys()
+            .filter(|&item| !referenced.contains(item))
+            .cloned()
+            .collect::<Vec<_>>();
+        for item in gone {
+            if let Some(Watched { handler, .. }) = watched.remove(&item) {
+                item.disconnect(handler);
+            }
+        }
+    }
+
+    /// Forgets everything, like when the window is closed.
+    fn clear(&self) {
+        self.latest_changed.borrow_mut().clear();
+        self.undo_stack.borrow_mut().clear();
+        self.redo_stack.borrow_mut().clear();
+        self.items.borrow_mut().clear();
+        self.prune();
+    }
+
+    fn update_actions(&self) {
+        self.undo_action
+            .set_enabled(!self.undo_stack.borrow().is_empty());
+        self.redo_action
+            .set_enabled(!self.redo_stack.borrow().is_empty());
+    }
+}
+
+/// Folds `change` into `entry` if both change the same property of the same item.
+fn merge(entry: Option<&mut Vec<Change>>, change: &Change) -> bool {
+    let (item, property, new) = match *change {
+        Change::Property {
+            ref item,
+            ref property,
+            ref new,
+            ..
+        } => (item, property, new),
+        _ => return false,
+    };
+
+    match entry.map(|entry| entry.as_mut_slice()) {
+        Some(
+            &mut [Change::Property {
+                item: ref last_item,
+                property: ref last_property,
+                new: ref mut last_new,
+                ..
+            }],
+        ) if last_item == item && last_property == property => {
+            *last_new = new.clone();
+            true
+        }
+        _ => false,
+    }
+}
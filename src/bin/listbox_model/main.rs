@@ -0,0 +1,716 @@
+//! # ListBox and ListModel Sample
+//!
+//! This sample demonstrates how to use gtk::ListBox in combination with
+//! gio::ListStore as a model with a custom row type.
+//!
+//! It sets up a gtk::ListBox containing, per row, a label, spinbutton and
+//! an edit button. The edit button allows to edit the underlying data structure
+//! and changes are taking place immediately in the listbox by making use of GObject
+//! property bindings.
+//!
+//! In addition it is possible to add new rows and delete old ones.
+//!
+//! Besides a name and count, each row has a date, a category, a flag and a colour. The
+//! dialogs show a fitting editor for each, and don't accept empty names or names another row
+//! already has, see the `editor` module.
+//!
+//! A search entry above the list only shows the rows with a matching name, and the rows can be
+//! sorted by name or count. Both leave the model as it is, see the `filter` module.
+//!
+//! Rows can be moved around by dragging them at the handle at their start, or with Alt+Up and
+//! Alt+Down, see the `reorder` module.
+//!
+//! The rows can be imported from and exported to CSV files, see the `transfer` module.
+//!
+//! Every change can be undone and redone again, with the buttons at the top or Ctrl+Z and
+//! Ctrl+Shift+Z, see the `history` module. After deleting an item, a message at the bottom
+//! offers to bring it back, for as long as that's what undoing would do.
+//!
+//! The rows are saved to a JSON file in the user's data directory whenever they change and
+//! are loaded from it again on the next start, see the `storage` module. If that file can't
+//! be read, it is moved aside and an info bar at the top of the window says what happened. The
+//! same info bar tells when the rows couldn't be saved.
+
+extern crate cairo;
+extern crate chrono;
+extern crate csv;
+extern crate gdk;
+#[macro_use]
+extern crate glib;
+extern crate gio;
+extern crate gtk;
+extern crate pango;
+extern crate serde;
+extern crate serde_json;
+
+use gio::prelude::*;
+use gtk::prelude::*;
+
+use gtk::ResponseType;
+
+use std::env::args;
+
+use editor::Editor;
+use filter::FilterBar;
+use history::History;
+use reorder::Reorder;
+use row_data::RowData;
+use storage::Storage;
+use toast::Toast;
+
+mod editor;
+mod filter;
+mod history;
+mod reorder;
+mod storage;
+mod toast;
+mod transfer;
+
+fn build_ui(application: &gtk::Application) {
+    let window = gtk::ApplicationWindow::new(application);
+
+    window.set_title("ListBox Model Sample");
+    window.set_border_width(10);
+    window.set_position(gtk::WindowPosition::Center);
+    window.set_default_size(320, 480);
+
+    let vbox = gtk::Box::new(gtk::Orientation::Vertical, 5);
+
+    // Create our list store and specify that the type stored in the
+    // list should be the RowData GObject we define at the bottom
+    let model = gio::ListStore::new(RowData::static_type());
+
+    // And then create the UI part, the listbox and bind the list store
+    // model to it. Whenever the UI needs to show a new row, e.g. because
+    // it was notified that the model changed, it will call the callback
+    // with the corresponding item from the model and will ask for a new
+    // gtk::ListBoxRow that should be displayed.
+    //
+    // The gtk::ListBoxRow can contain any possible widgets.
+    //
+    // The model is bound through the filter bar, which needs to know the item of each row to
+    // search and sort them.
+    let listbox = gtk::ListBox::new();
+    let filter_bar = FilterBar::new(&listbox, &model);
+    filter_bar.bind(
+        clone!(@weak window, @weak model => @default-panic, move |item| {
+            let box_ = gtk::ListBoxRow::new();
+            let item = item.downcast_ref::<RowData>().expect("Row data is of wrong type");
+
+            let hbox = gtk::Box::new(gtk::Orientation::Horizontal, 5);
+
+            // The handle the row can be dragged at to move it somewhere else
+            hbox.pack_start(&reorder::drag_handle(item, &model), false, false, 0);
+
+            // The colour and the flag can be changed right in the row as well
+            let color_button = gtk::ColorButton::new();
+            item.bind_property("color", &color_button, "rgba")
+                .flags(glib::BindingFlags::DEFAULT | glib::BindingFlags::SYNC_CREATE | glib::BindingFlags::BIDIRECTIONAL)
+                .build();
+            hbox.pack_start(&color_button, false, false, 0);
+
+            let flagged_button = gtk::CheckButton::new();
+            flagged_button.set_tooltip_text(Some("Flagged"));
+            item.bind_property("flagged", &flagged_button, "active")
+                .flags(glib::BindingFlags::DEFAULT | glib::BindingFlags::SYNC_CREATE | glib::BindingFlags::BIDIRECTIONAL)
+                .build();
+            hbox.pack_start(&flagged_button, false, false, 0);
+
+            // Create the label and spin button that shows the two values
+            // of the item. We bind the properties for the two values to the
+            // corresponding properties of the widgets so that they are automatically
+            // updated whenever the item is changing. By specifying SYNC_CREATE the
+            // widget will automatically get the initial value of the item set.
+            //
+            // In case of the spin button the binding is bidirectional, that is any
+            // change of value in the spin button will be automatically reflected in
+            // the item.
+            let label = gtk::Label::new(None);
+            label.set_xalign(0.0);
+            item.bind_property("name", &label, "label")
+                .flags(glib::BindingFlags::DEFAULT | glib::BindingFlags::SYNC_CREATE)
+                .build();
+
+            // Below the name go the category and date, if there is one. Transforming the
+            // values lets them be bound to labels as well
+            let category_label = gtk::Label::new(None);
+            category_label.get_style_context().add_class("dim-label");
+            item.bind_property("category", &category_label, "label")
+                .flags(glib::BindingFlags::DEFAULT | glib::BindingFlags::SYNC_CREATE)
+                .transform_to(|_, value| {
+                    let category = value.get_some::<row_data::Category>().ok()?;
+                    Some(category.label().to_value())
+                })
+                .build();
+
+            let date_label = gtk::Label::new(None);
+            date_label.get_style_context().add_class("dim-label");
+            item.bind_property("date", &date_label, "label")
+                .flags(glib::BindingFlags::DEFAULT | glib::BindingFlags::SYNC_CREATE)
+                .transform_to(|_, value| {
+                    let date = value.get::<glib::DateTime>().ok()?;
+                    Some(date.map(|date| row_data::format_date(&date)).unwrap_or_default().to_value())
+                })
+                .build();
+
+            let details = gtk::Box::new(gtk::Orientation::Horizontal, 10);
+            details.pack_start(&category_label, false, false, 0);
+            details.pack_start(&date_label, false, false, 0);
+
+            let text = gtk::Box::new(gtk::Orientation::Vertical, 0);
+            text.pack_start(&label, false, false, 0);
+            text.pack_start(&details, false, false, 0);
+            hbox.pack_start(&text, true, true, 0);
+
+            let spin_button = gtk::SpinButton::with_range(0.0, 100.0, 1.0);
+            item.bind_property("count", &spin_button, "value")
+                .flags(glib::BindingFlags::DEFAULT | glib::BindingFlags::SYNC_CREATE | glib::BindingFlags::BIDIRECTIONAL)
+                .build();
+        hbox.pack_start(&spin_button, false, false, 0);
+
+        // When the edit button is clicked, a new modal dialog is created for editing
+        // the corresponding row
+        let edit_button = gtk::Button::with_label("Edit");
+        edit_button.connect_clicked(clone!(@weak window, @weak model, @strong item => move |_| {
+            let dialog = gtk::Dialog::with_buttons(Some("Edit Item"), Some(&window), gtk::DialogFlags::MODAL,
+                &[("Close", ResponseType::Close)]);
+            dialog.set_default_response(ResponseType::Close);
+            dialog.connect_response(|dialog, _| dialog.close());
+
+            let content_area = dialog.get_content_area();
+
+            // Similarly to the widgets inside the listbox, the editors in the edit dialog
+            // are connected via property bindings to the item. Any changes will be
+            // immediately reflected inside the item and by the listbox, except for names
+            // that can't be used
+            let editor = Editor::new(&item, &model);
+            editor.grid.set_border_width(10);
+
+            // Activating the entry (enter) will send response `ResponseType::Close` to the dialog
+            editor.name_entry.connect_activate(clone!(@weak dialog => move |_| {
+                dialog.response(ResponseType::Close);
+            }));
+            content_area.add(&editor.grid);
+
+            dialog.show_all();
+        }));
+        hbox.pack_start(&edit_button, false, false, 0);
+
+        box_.add(&hbox);
+
+        // When a row is activated (select + enter) we simply emit the clicked
+        // signal on the corresponding edit button to open the edit dialog
+        box_.connect_activate(clone!(@weak edit_button => move |_| {
+            edit_button.emit_clicked();
+        }));
+
+        box_.show_all();
+
+        box_.upcast::<gtk::Widget>()
+    }));
+
+    Reorder::new(&listbox, &model, &filter_bar);
+
+    let scrolled_window = gtk::ScrolledWindow::new(gtk::NONE_ADJUSTMENT, gtk::NONE_ADJUSTMENT);
+    scrolled_window.add(&listbox);
+
+    // Messages about what just happened are shown on top of the bottom of the list
+    let toast = Toast::new();
+    let overlay = gtk::Overlay::new();
+    overlay.add(&scrolled_window);
+    overlay.add_overlay(&toast.revealer);
+
+    let hbox = gtk::Box::new(gtk::Orientation::Horizontal, 5);
+
+    // The add button opens a new dialog which is basically the same as the edit
+    // dialog, except that we don't have a corresponding item yet at that point
+    // and only create it once the Ok button in the dialog is clicked, and only
+    // then add it to the model. Once added to the model, it will immediately
+    // appear in the listbox UI
+    let add_button = gtk::Button::with_label("Add");
+    add_button.connect_clicked(clone!(@weak window, @weak model => move |_| {
+            let dialog = gtk::Dialog::with_buttons(Some("Add Item"), Some(&window), gtk::DialogFlags::MODAL,
+                &[("Ok", ResponseType::Ok), ("Cancel", ResponseType::Cancel)]);
+            dialog.set_default_response(ResponseType::Ok);
+
+            let content_area = dialog.get_content_area();
+
+            // The new item is edited just like an existing one, it's only added to the model
+            // once Ok is clicked. Until it has a name that can be used that isn't possible
+            let item = RowData::new("", 0);
+            let editor = Editor::new(&item, &model);
+            editor.grid.set_border_width(10);
+            dialog.set_response_sensitive(ResponseType::Ok, editor.is_valid());
+            editor.name_entry.connect_changed(clone!(@weak dialog, @weak editor => move |_| {
+                dialog.set_response_sensitive(ResponseType::Ok, editor.is_valid());
+            }));
+            editor.name_entry.connect_activate(clone!(@weak dialog, @weak editor => move |_| {
+                if editor.is_valid() {
+                    dialog.response(ResponseType::Ok);
+                }
+            }));
+            content_area.add(&editor.grid);
+
+            dialog.connect_response(clone!(@weak model, @strong editor => move |dialog, resp| {
+                if editor.is_valid() && resp == ResponseType::Ok {
+                    model.append(&item);
+                }
+                dialog.close();
+            }));
+
+            dialog.show_all();
+    }));
+
+    hbox.add(&add_button);
+
+    // Via the delete button we delete the item from the model that
+    // is at the index of the selected row. Also deleting from the
+    // model is immediately reflected in the listbox.
+    let delete_button = gtk::Button::with_label("Delete");
+    // The index of the row can't be used for the model here, as it's different while sorted.
+    // Holding on to the filter bar here also keeps it around for as long as the window
+    delete_button.connect_clicked(clone!(@weak model, @weak listbox, @strong filter_bar, @strong toast => move |_| {
+        let selected = listbox.get_selected_row().and_then(|row| filter_bar.get_item(&row));
+
+        if let Some(idx) = selected.and_then(|selected| position_of(&model, &selected)) {
+            model.remove(idx);
+            toast.show("Item deleted");
+        }
+    }));
+    hbox.add(&delete_button);
+
+    // The rows can be exchanged with spreadsheets as CSV files. Importing shows a preview
+    // first, where the imported rows can also be chosen to replace the existing ones
+    let import_button = gtk::Button::with_label("Import…");
+    import_button.connect_clicked(clone!(@weak window, @weak model => move |_| {
+        transfer::import(&window, &model);
+    }));
+    hbox.add(&import_button);
+
+    let export_button = gtk::Button::with_label("Export…");
+    export_button.connect_clicked(clone!(@weak window, @weak model => move |_| {
+        transfer::export(&window, &model);
+    }));
+    hbox.add(&export_button);
+
+    // Both buttons are only sensitive while there is something to undo or redo, as that is
+    // when their actions are enabled
+    let undo_button = gtk::Button::from_icon_name(Some("edit-undo-symbolic"), gtk::IconSize::Button);
+    undo_button.set_tooltip_text(Some("Undo"));
+    undo_button.set_action_name(Some("win.undo"));
+    hbox.pack_end(&undo_button, false, false, 0);
+
+    let redo_button = gtk::Button::from_icon_name(Some("edit-redo-symbolic"), gtk::IconSize::Button);
+    redo_button.set_tooltip_text(Some("Redo"));
+    redo_button.set_action_name(Some("win.redo"));
+    hbox.pack_end(&redo_button, false, false, 0);
+
+    vbox.pack_start(&hbox, false, false, 0);
+    vbox.pack_start(&filter_bar.container, false, false, 0);
+    vbox.pack_start(&overlay, true, true, 0);
+    vbox.pack_start(&filter_bar.status, false, false, 0);
+
+    window.add(&vbox);
+
+    // Fill the model from the previous run, and only start with a few generated rows if
+    // there was none
+    let storage = Storage::new(&model);
+    vbox.pack_start(&storage.info_bar, false, false, 0);
+    vbox.reorder_child(&storage.info_bar, 0);
+    if !storage.load() {
+        for i in 0..10 {
+            model.append(&RowData::new(&format!("Name {}", i), i * 10));
+        }
+    }
+    storage.watch();
+
+    // Only record changes after loading, there's no undoing that. The message offering to
+    // bring back a deleted item goes away once undoing would revert something else
+    let history = History::new(&model, &window);
+    history.connect_latest_changed(clone!(@weak toast => move || toast.hide()));
+    application.set_accels_for_action("win.undo", &["<Primary>z"]);
+    application.set_accels_for_action("win.redo", &["<Primary><Shift>z"]);
+
+    // Don't lose the last changes if the window is closed before they were saved
+    application.connect_shutdown(move |_| storage.flush());
+
+    window.show_all();
+}
+
+/// Where `item` is in `model`, if it's still there.
+fn position_of(model: &gio::ListStore, item: &RowData) -> Option<u32> {
+    let item = item.clone().upcast::<glib::Object>();
+    (0..model.get_n_items()).find(|&position| model.get_object(position).as_ref() == Some(&item))
+}
+
+fn main() {
+    let application = gtk::Application::new(
+        Some("com.github.gtk-rs.examples.listbox-model"),
+        Default::default(),
+    )
+    .expect("Initialization failed...");
+
+    application.connect_activate(|app| {
+        // The rows are saved by the first window, a second one would overwrite its changes
+        match app.get_active_window() {
+            Some(window) => window.present(),
+            None => build_ui(app),
+        }
+    });
+
+    application.run(&args().collect::<Vec<_>>());
+}
+
+// Our GObject subclass for carrying a name, count and a few more details for the
+// ListBox model
+//
+// All values are stored in a RefCell to allow for interior mutability
+// and are exposed via normal GObject properties. This allows us to use property
+// bindings below to bind the values with what widgets display in the UI
+mod row_data {
+    use super::*;
+
+    use chrono::{Datelike, NaiveDate};
+    use glib::subclass;
+    use glib::subclass::prelude::*;
+    use glib::translate::*;
+
+    // What kind of item a row is. Registering it as a GObject enum type lets it be used as
+    // the type of a property, which only accepts the values listed here
+    #[derive(Clone, Copy, Debug, PartialEq, Eq, GEnum)]
+    #[genum(type_name = "RowDataCategory")]
+    pub enum Category {
+        #[genum(name = "Other", nick = "other")]
+        Other,
+        #[genum(name = "Personal", nick = "personal")]
+        Personal,
+        #[genum(name = "Work", nick = "work")]
+        Work,
+        #[genum(name = "Shopping", nick = "shopping")]
+        Shopping,
+    }
+
+    impl Category {
+        pub const ALL: [Category; 4] = [
+            Category::Other,
+            Category::Personal,
+            Category::Work,
+            Category::Shopping,
+        ];
+
+        // The name used for the category in files and as ID in combo boxes
+        pub fn nick(self) -> &'static str {
+            match self {
+                Category::Other => "other",
+                Category::Personal => "personal",
+                Category::Work => "work",
+                Category::Shopping => "shopping",
+            }
+        }
+
+        pub fn from_nick(nick: &str) -> Option<Category> {
+            Category::ALL.iter().cloned().find(|category| category.nick() == nick)
+        }
+
+        pub fn label(self) -> &'static str {
+            match self {
+                Category::Other => "Other",
+                Category::Personal => "Personal",
+                Category::Work => "Work",
+                Category::Shopping => "Shopping",
+            }
+        }
+    }
+
+    // The colour rows get unless another one is picked
+    pub const DEFAULT_COLOR: gdk::RGBA = gdk::RGBA {
+        red: 0.21,
+        green: 0.52,
+        blue: 0.89,
+        alpha: 1.0,
+    };
+
+    // Implementation sub-module of the GObject
+    mod imp {
+        use super::*;
+        use std::cell::RefCell;
+
+        // The actual data structure that stores our values. This is not accessible
+        // directly from the outside.
+        pub struct RowData {
+            name: RefCell<Option<String>>,
+            count: RefCell<u32>,
+            date: RefCell<Option<glib::DateTime>>,
+            category: RefCell<Category>,
+            flagged: RefCell<bool>,
+            color: RefCell<gdk::RGBA>,
+        }
+
+        // GObject property definitions for our values. They only notify about actual
+        // changes, so setting the same value again isn't saved or recorded for undo.
+        //
+        // The types of the properties make sure only valid values end up in them, like
+        // only counts between 0 and 100 or one of the categories. Whether a name is unique
+        // depends on the other rows though, which is checked by the editors
+        static PROPERTIES: [subclass::Property; 6] = [
+            subclass::Property("name", |name| {
+                glib::ParamSpec::string(
+                    name,
+                    "Name",
+                    "Name",
+                    None, // Default value
+                    glib::ParamFlags::READWRITE | glib::ParamFlags::EXPLICIT_NOTIFY,
+                )
+            }),
+            subclass::Property("count", |name| {
+                glib::ParamSpec::uint(
+                    name,
+                    "Count",
+                    "Count",
+                    0,
+                    100,
+                    0, // Allowed range and default value
+                    glib::ParamFlags::READWRITE | glib::ParamFlags::EXPLICIT_NOTIFY,
+                )
+            }),
+            subclass::Property("date", |name| {
+                glib::ParamSpec::boxed(
+                    name,
+                    "Date",
+                    "Date, at midnight local time",
+                    glib::DateTime::static_type(),
+                    glib::ParamFlags::READWRITE | glib::ParamFlags::EXPLICIT_NOTIFY,
+                )
+            }),
+            subclass::Property("category", |name| {
+                glib::ParamSpec::enum_(
+                    name,
+                    "Category",
+                    "Category",
+                    Category::static_type(),
+                    Category::Other as i32,
+                    glib::ParamFlags::READWRITE | glib::ParamFlags::EXPLICIT_NOTIFY,
+                )
+            }),
+            subclass::Property("flagged", |name| {
+                glib::ParamSpec::boolean(
+                    name,
+                    "Flagged",
+                    "Flagged",
+                    false,
+                    glib::ParamFlags::READWRITE | glib::ParamFlags::EXPLICIT_NOTIFY,
+                )
+            }),
+            subclass::Property("color", |name| {
+                glib::ParamSpec::boxed(
+                    name,
+                    "Color",
+                    "Color",
+                    gdk::RGBA::static_type(),
+                    glib::ParamFlags::READWRITE | glib::ParamFlags::EXPLICIT_NOTIFY,
+                )
+            }),
+        ];
+
+        // Basic declaration of our type for the GObject type system
+        impl ObjectSubclass for RowData {
+            const NAME: &'static str = "RowData";
+            type ParentType = glib::Object;
+            type Instance = subclass::simple::InstanceStruct<Self>;
+            type Class = subclass::simple::ClassStruct<Self>;
+
+            glib_object_subclass!();
+
+            // Called exactly once before the first instantiation of an instance. This
+            // sets up any type-specific things, in this specific case it installs the
+            // properties so that GObject knows about their existence and they can be
+            // used on instances of our type
+            fn class_init(klass: &mut Self::Class) {
+                klass.install_properties(&PROPERTIES);
+            }
+
+            // Called once at the very beginning of instantiation of each instance and
+            // creates the data structure that contains all our state
+            fn new() -> Self {
+                Self {
+                    name: RefCell::new(None),
+                    count: RefCell::new(0),
+                    date: RefCell::new(None),
+                    category: RefCell::new(Category::Other),
+                    flagged: RefCell::new(false),
+                    color: RefCell::new(DEFAULT_COLOR),
+                }
+            }
+        }
+
+        // The ObjectImpl trait provides the setters/getters for GObject properties.
+        // Here we need to provide the values that are internally stored back to the
+        // caller, or store whatever new value the caller is providing.
+        //
+        // This maps between the GObject properties and our internal storage of the
+        // corresponding values of the properties.
+        impl ObjectImpl for RowData {
+            glib_object_impl!();
+
+            fn set_property(&self, obj: &glib::Object, id: usize, value: &glib::Value) {
+                let prop = &PROPERTIES[id];
+
+                match *prop {
+                    subclass::Property("name", ..) => {
+                        let name = value
+                            .get()
+                            .expect("type conformity checked by `Object::set_property`");
+                        if self.name.replace(name.clone()) != name {
+                            obj.notify("name");
+                        }
+                    }
+                    subclass::Property("count", ..) => {
+                        let count = value
+                            .get_some()
+                            .expect("type conformity checked by `Object::set_property`");
+                        if self.count.replace(count) != count {
+                            obj.notify("count");
+                        }
+                    }
+                    subclass::Property("date", ..) => {
+                        let date = value
+                            .get()
+                            .expect("type conformity checked by `Object::set_property`");
+                        if self.date.replace(date.clone()) != date {
+                            obj.notify("date");
+                        }
+                    }
+                    subclass::Property("category", ..) => {
+                        let category = value
+                            .get_some()
+                            .expect("type conformity checked by `Object::set_property`");
+                        if self.category.replace(category) != category {
+                            obj.notify("category");
+                        }
+                    }
+                    subclass::Property("flagged", ..) => {
+                        let flagged = value
+                            .get_some()
+                            .expect("type conformity checked by `Object::set_property`");
+                        if self.flagged.replace(flagged) != flagged {
+                            obj.notify("flagged");
+                        }
+                    }
+                    subclass::Property("color", ..) => {
+                        // Unsetting the colour goes back to the default one
+                        let color = value
+                            .get()
+                            .expect("type conformity checked by `Object::set_property`")
+                            .unwrap_or(DEFAULT_COLOR);
+                        if self.color.replace(color) != color {
+                            obj.notify("color");
+                        }
+                    }
+                    _ => unimplemented!(),
+                }
+            }
+
+            fn get_property(&self, _obj: &glib::Object, id: usize) -> Result<glib::Value, ()> {
+                let prop = &PROPERTIES[id];
+
+                match *prop {
+                    subclass::Property("name", ..) => Ok(self.name.borrow().to_value()),
+                    subclass::Property("count", ..) => Ok(self.count.borrow().to_value()),
+                    subclass::Property("date", ..) => Ok(self.date.borrow().to_value()),
+                    subclass::Property("category", ..) => Ok(self.category.borrow().to_value()),
+                    subclass::Property("flagged", ..) => Ok(self.flagged.borrow().to_value()),
+                    subclass::Property("color", ..) => Ok(self.color.borrow().to_value()),
+                    _ => unimplemented!(),
+                }
+            }
+        }
+    }
+
+    // Public part of the RowData type. This behaves like a normal gtk-rs-style GObject
+    // binding
+    glib_wrapper! {
+        pub struct RowData(Object<subclass::simple::InstanceStruct<imp::RowData>, subclass::simple::ClassStruct<imp::RowData>, RowDataClass>);
+
+        match fn {
+            get_type => || imp::RowData::get_type().to_glib(),
+        }
+    }
+
+    // Constructor for new instances. This simply calls glib::Object::new() with
+    // initial values for our two main properties and then returns the new instance,
+    // the others start out with their defaults
+    impl RowData {
+        pub fn new(name: &str, count: u32) -> RowData {
+            glib::Object::new(Self::static_type(), &[("name", &name), ("count", &count)])
+                .expect("Failed to create row data")
+                .downcast()
+                .expect("Created row data is of wrong type")
+        }
+
+        pub fn get_name(&self) -> String {
+            self.get_property("name")
+                .expect("RowData has a name")
+                .get::<String>()
+                .expect("Name is a string")
+                .unwrap_or_default()
+        }
+
+        pub fn get_count(&self) -> u32 {
+            self.get_property("count")
+                .expect("RowData has a count")
+                .get_some::<u32>()
+                .expect("Count is a number")
+        }
+
+        pub fn get_date(&self) -> Option<glib::DateTime> {
+            self.get_property("date")
+                .expect("RowData has a date")
+                .get::<glib::DateTime>()
+                .expect("Date is a DateTime")
+        }
+
+        pub fn get_category(&self) -> Category {
+            self.get_property("category")
+                .expect("RowData has a category")
+                .get_some::<Category>()
+                .expect("Category is a Category")
+        }
+
+        pub fn get_flagged(&self) -> bool {
+            self.get_property("flagged")
+                .expect("RowData has a flag")
+                .get_some::<bool>()
+                .expect("Flagged is a bool")
+        }
+
+        pub fn get_color(&self) -> gdk::RGBA {
+            self.get_property("color")
+                .expect("RowData has a color")
+                .get::<gdk::RGBA>()
+                .expect("Color is a RGBA")
+                .unwrap_or(DEFAULT_COLOR)
+        }
+    }
+
+    // Dates are written as YYYY-MM-DD wherever they are shown or saved
+    pub fn format_date(date: &glib::DateTime) -> String {
+        let (year, month, day) = date.get_ymd();
+        format!("{:04}-{:02}-{:02}", year, month, day)
+    }
+
+    pub fn parse_date(text: &str) -> Option<glib::DateTime> {
+        let date = NaiveDate::parse_from_str(text.trim(), "%Y-%m-%d").ok()?;
+        // The years GDateTime can represent
+        if !(1..=9999).contains(&date.year()) {
+            return None;
+        }
+
+        Some(new_date(date.year(), date.month() as i32, date.day() as i32))
+    }
+
+    // Dates are kept at midnight UTC. Local midnight doesn't exist on the days some time zones
+    // switch to daylight saving time, and creating it would fail
+    pub fn new_date(year: i32, month: i32, day: i32) -> glib::DateTime {
+        glib::DateTime::new_utc(year, month, day, 0, 0, 0.0)
+    }
+}
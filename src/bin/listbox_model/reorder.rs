@@ -0,0 +1,283 @@
+//! Moving rows around, by dragging them at their handle or with Alt+Up and Alt+Down.
+//!
+//! Either way the item is removed from the model and inserted again at its new position, and
+//! the list box follows along like it does for any other change. This only makes sense in the
+//! order of the model, so nothing can be moved while the rows are sorted.
+
+use std::cell::RefCell;
+use std::rc::Rc;
+use std::str;
+
+use cairo;
+use gdk;
+use gdk::keys::constants as key;
+use gio;
+use gio::prelude::*;
+use gtk;
+use gtk::prelude::*;
+
+use filter::FilterBar;
+use position_of;
+use row_data::RowData;
+
+// Only rows of this very list can be dropped on it
+const TARGET: &str = "LISTBOX_MODEL_ROW";
+
+fn targets() -> Vec<gtk::TargetEntry> {
+    vec![gtk::TargetEntry::new(TARGET, gtk::TargetFlags::SAME_APP, 0)]
+}
+
+/// Creates the handle `item` can be dragged at. What is dragged is the item's position in
+/// `model`.
+pub fn drag_handle(item: &RowData, model: &gio::ListStore) -> gtk::EventBox {
+    let handle = gtk::EventBox::new();
+    let image = gtk::Image::from_icon_name(Some("list-drag-handle-symbolic"), gtk::IconSize::Menu);
+    handle.add(&image);
+    handle.set_tooltip_text(Some("Drag to move, or use Alt+Up and Alt+Down"));
+
+    handle.drag_source_set(
+        gdk::ModifierType::BUTTON1_MASK,
+        &targets(),
+        gdk::DragAction::MOVE,
+    );
+    handle.connect_drag_begin(|_, context| {
+        context.drag_set_icon_name("list-drag-handle-symbolic", 0, 0);
+    });
+    handle.connect_drag_data_get(
+        clone!(@weak item, @weak model => move |_, _, selection, _, _| {
+            if let Some(position) = position_of(&model, &item) {
+                selection.set(&selection.get_target(), 8, position.to_string().as_bytes());
+            }
+        }),
+    );
+
+    handle
+}
+
+/// Where a dragged row would end up: before or after the row under the pointer.
+struct DropTarget {
+    row: gtk::ListBoxRow,
+    after: bool,
+}
+
+pub struct Reorder {
+    listbox: gtk::ListBox,
+    model: gio::ListStore,
+    filter_bar: Rc<FilterBar>,
+    drop_target: RefCell<Option<DropTarget>>,
+}
+
+impl Reorder {
+    /// Lets the rows of `listbox` be moved around. The list box keeps the returned value around
+    /// until it's destroyed.
+    pub fn new(
+        listbox: &gtk::ListBox,
+        model: &gio::ListStore,
+        filter_bar: &Rc<FilterBar>,
+    ) -> Rc<Self> {
+        let reorder = Rc::new(Reorder {
+            listbox: listbox.clone(),
+            model: model.clone(),
+            filter_bar: filter_bar.clone(),
+            drop_target: RefCell::new(None),
+        });
+
+        reorder.connect_drop();
+        reorder.connect_keys();
+
+        listbox.connect_destroy(clone!(@strong reorder => move |_| {
+            reorder.drop_target.replace(None);
+        }));
+        reorder
+    }
+
+    fn connect_drop(self: &Rc<Self>) {
+        self.listbox.drag_dest_set(
+            gtk::DestDefaults::MOTION | gtk::DestDefaults::DROP,
+            &targets(),
+            gdk::DragAction::MOVE,
+        );
+
+        self.listbox.connect_drag_motion(clone!(
+            @weak self as reorder => @default-return Inhibit(false),
+            move |_, context, _, y, time| {
+                let drop_target = reorder.find_drop_target(y);
+                let allowed = drop_target.is_some();
+                reorder.drop_target.replace(drop_target);
+                reorder.listbox.queue_draw();
+                // With `DestDefaults::MOTION` the drag was already accepted for matching
+                // targets, take that back while the rows are sorted
+                if !allowed {
+                    context.drag_status(gdk::DragAction::empty(), time);
+                }
+                Inhibit(allowed)
+            }
+        ));
+
+        self.listbox
+            .connect_drag_leave(clone!(@weak self as reorder => move |_, _, _| {
+                reorder.drop_target.replace(None);
+                reorder.listbox.queue_draw();
+            }));
+
+        self.listbox.connect_drag_data_received(
+            clone!(@weak self as reorder => move |_, _, _, y, selection, _, _| {
+                reorder.drop_target.replace(None);
+                reorder.listbox.queue_draw();
+                // The rows may have been sorted since the drag started
+                if reorder.filter_bar.is_sorted() {
+                    return;
+                }
+
+                let from = str::from_utf8(&selection.get_data())
+                    .ok()
+                    .and_then(|data| data.parse::<u32>().ok());
+                let to = reorder
+                    .find_drop_target(y)
+                    .and_then(|drop_target| reorder.drop_position(&drop_target));
+                if let (Some(from), Some(to)) = (from, to) {
+                    reorder.move_item(from, to);
+                }
+            }),
+        );
+
+        // Drawn after the rows, so the line ends up on top of them
+        self.listbox
+            .connect_local(
+                "draw",
+                true,
+                clone!(@weak self as reorder => @default-return None, move |values| {
+                    let cr = values[1]
+                        .get::<cairo::Context>()
+                        .expect("Draw signal has a cairo context")
+                        .expect("Draw signal has a cairo context");
+                    reorder.draw_drop_target(&cr);
+                    None
+                }),
+            )
+            .expect("Couldn't connect to draw");
+    }
+
+    fn connect_keys(self: &Rc<Self>) {
+        self.listbox.connect_key_press_event(
+            clone!(@weak self as reorder => @default-return Inhibit(false), move |_, event| {
+                if !event.get_state().contains(gdk::ModifierType::MOD1_MASK) {
+                    return Inhibit(false);
+                }
+                match event.get_keyval() {
+                    key::Up | key::KP_Up => Inhibit(reorder.move_selected(false)),
+                    key::Down | key::KP_Down => Inhibit(reorder.move_selected(true)),
+                    _ => Inhibit(false),
+                }
+            }),
+        );
+    }
+
+    /// Works out what a row dropped at `y` would be placed next to: the row under the pointer,
+    /// or the last one shown when below all rows.
+    fn find_drop_target(&self, y: i32) -> Option<DropTarget> {
+        if self.filter_bar.is_sorted() {
+            return None;
+        }
+
+        match self.listbox.get_row_at_y(y) {
+            Some(row) => {
+                let allocation = row.get_allocation();
+                let after = y > allocation.y + allocation.height / 2;
+                Some(DropTarget { row, after })
+            }
+            None => self
+                .listbox
+                .get_children()
+                .into_iter()
+                .rev()
+                .filter(|child| child.get_child_visible())
+                .find_map(|child| child.downcast::<gtk::ListBoxRow>().ok())
+                .map(|row| DropTarget { row, after: true }),
+        }
+    }
+
+    /// The position in the model a row dropped on `drop_target` goes to.
+    fn drop_position(&self, drop_target: &DropTarget) -> Option<u32> {
+        let item = self.filter_bar.get_item(&drop_target.row)?;
+        let position = position_of(&self.model, &item)?;
+        Some(if drop_target.after {
+            position + 1
+        } else {
+            position
+        })
+    }
+
+    fn draw_drop_target(&self, cr: &cairo::Context) {
+        let drop_target = self.drop_target.borrow();
+        let drop_target = match *drop_target {
+            Some(ref drop_target) => drop_target,
+            None => return,
+        };
+
+        let allocation = drop_target.row.get_allocation();
+        let width = f64::from(self.listbox.get_allocated_width());
+        let height = f64::from(self.listbox.get_allocated_height());
+        let y = if drop_target.after {
+            allocation.y + allocation.height
+        } else {
+            allocation.y
+        };
+        // Keep the whole line visible at the very top and bottom
+        let y = f64::from(y).max(1.0).min(height - 1.0);
+
+        let color = self
+            .listbox
+            .get_style_context()
+            .get_color(gtk::StateFlags::NORMAL);
+        cr.set_source_rgba(color.red, color.green, color.blue, color.alpha);
+        cr.rectangle(0.0, y - 1.0, width, 2.0);
+        cr.fill();
+    }
+
+    /// Moves the selected row one up or down. Returns whether there was something to move.
+    fn move_selected(&self, down: bool) -> bool {
+        if self.filter_bar.is_sorted() {
+            return false;
+        }
+
+        let position = match self
+            .listbox
+            .get_selected_row()
+            .and_then(|row| self.filter_bar.get_item(&row))
+            .and_then(|item| position_of(&self.model, &item))
+        {
+            Some(position) => position,
+            None => return false,
+        };
+
+        if down && position + 1 < self.model.get_n_items() {
+            self.move_item(position, position + 2);
+        } else if !down && position > 0 {
+            self.move_item(position, position - 1);
+        }
+        true
+    }
+
+    /// Moves the item at `from` so it ends up in front of what is at `to` now.
+    fn move_item(&self, from: u32, to: u32) {
+        let item = match self.model.get_object(from) {
+            Some(item) => item,
+            None => return,
+        };
+        let to = if to > from { to - 1 } else { to };
+        if to == from {
+            return;
+        }
+
+        self.model.remove(from);
+        self.model.insert(to, &item);
+
+        // The row got recreated for its new place, select that one again. Not being sorted,
+        // the rows are in the same order as the model.
+        if let Some(row) = self.listbox.get_row_at_index(to as i32) {
+            self.listbox.select_row(Some(&row));
+            row.grab_focus();
+        }
+    }
+}
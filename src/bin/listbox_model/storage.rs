@@ -0,0 +1,421 @@
+//! Keeps the rows in a JSON file in the user's data directory, so they survive a restart.
+//!
+//! The file is written a moment after the last change, through `gio::File::replace_contents`.
+//! That writes to a temporary file first and only then moves it over the old one, keeping the
+//! previous version around as a backup in case the new one can't be read later on.
+//!
+//! Anything going wrong on the way is shown in an info bar, which is up to the window to place.
+
+use std::cell::RefCell;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+use std::str;
+
+use gdk;
+use gio;
+use gio::prelude::*;
+use glib;
+use gtk;
+use gtk::prelude::*;
+use serde::{Deserialize, Serialize};
+use serde_json;
+
+use row_data::{self, Category, RowData};
+
+// How long to wait after a change before saving, so a burst of changes like holding down the
+// arrow of a spin button is written out just once.
+const SAVE_DELAY_MS: u32 = 500;
+// Version 1 only had names and counts, the other values are left at their defaults for it
+const FORMAT_VERSION: u32 = 2;
+
+pub struct Storage {
+    pub info_bar: gtk::InfoBar,
+    info_label: gtk::Label,
+    model: gio::ListStore,
+    path: PathBuf,
+    save_source: RefCell<Option<glib::SourceId>>,
+    /// Items whose changes get saved already. A row that is moved around gets removed from the
+    /// model and inserted again, and shouldn't end up being watched twice.
+    watched: RefCell<Vec<glib::WeakRef<glib::Object>>>,
+}
+
+impl Storage {
+    pub fn new(model: &gio::ListStore) -> Rc<Self> {
+        let data_dir = glib::get_user_data_dir().expect("Couldn't get user data directory");
+
+        let info_bar = gtk::InfoBar::new();
+        info_bar.set_message_type(gtk::MessageType::Warning);
+        info_bar.set_show_close_button(true);
+        let info_label = gtk::Label::new(None);
+        info_label.set_line_wrap(true);
+        info_label.set_xalign(0.0);
+        info_label.show();
+        info_bar.get_content_area().add(&info_label);
+        info_bar.connect_response(|info_bar, _| info_bar.hide());
+        // Only shown when there's something to tell
+        info_bar.set_no_show_all(true);
+
+        Rc::new(Storage {
+            info_bar,
+            info_label,
+            model: model.clone(),
+            path: data_dir.join("gtk-rs-listbox-model").join("rows.json"),
+            save_source: RefCell::new(None),
+            watched: RefCell::new(Vec::new()),
+        })
+    }
+
+    /// Fills the model from the file. Returns `false` if nothing was saved yet.
+    ///
+    /// A file that can't be read is moved aside, so it doesn't get overwritten by the next save,
+    /// and the backup is loaded instead if there is a good one. The info bar says what happened.
+    pub fn load(&self) -> bool {
+        let err = match read_rows(&self.path) {
+            Ok(Some(rows)) => {
+                self.append(&rows);
+                return true;
+            }
+            Ok(None) => return false,
+            Err(err) => err,
+        };
+
+        let mut corrupt = self.path.clone().into_os_string();
+        corrupt.push(".corrupt");
+        let corrupt = PathBuf::from(corrupt);
+        let mut message = match fs::rename(&self.path, &corrupt) {
+            Ok(()) => format!(
+                "The saved list couldn't be read ({}), it was moved to {}.",
+                err,
+                corrupt.display()
+            ),
+            Err(rename_err) => format!(
+                "The saved list couldn't be read ({}), nor moved aside ({}).",
+                err, rename_err
+            ),
+        };
+        match read_rows(&backup_path(&self.path)) {
+            Ok(Some(rows)) => {
+                self.append(&rows);
+                message.push_str(" The previous version was restored instead.");
+            }
+            _ => message.push_str(" Starting over with an empty list."),
+        }
+        self.show_error(&message);
+        true
+    }
+
+    fn show_error(&self, message: &str) {
+        self.info_label.set_text(message);
+        self.info_bar.show();
+    }
+
+    fn append(&self, rows: &[RowData]) {
+        for row in rows {
+            self.model.append(row);
+        }
+    }
+
+    /// Saves the model shortly after it or any of its items changed.
+    pub fn watch(self: &Rc<Self>) {
+        for position in 0..self.model.get_n_items() {
+            self.watch_item(position);
+        }
+
+        self.model.connect_items_changed(
+            clone!(@weak self as storage => move |_, position, _, added| {
+                for position in position..position + added {
+                    storage.watch_item(position);
+                }
+                storage.queue_save();
+            }),
+        );
+    }
+
+    fn watch_item(self: &Rc<Self>, position: u32) {
+        let item = match self.model.get_object(position) {
+            Some(item) => item,
+            None => return,
+        };
+
+        let mut watched = self.watched.borrow_mut();
+        watched.retain(|watched| watched.upgrade().is_some());
+        if watched
+            .iter()
+            .any(|watched| watched.upgrade().as_ref() == Some(&item))
+        {
+            return;
+        }
+        watched.push(item.downgrade());
+
+        item.connect_local(
+            "notify",
+            false,
+            clone!(@weak self as storage => @default-return None, move |_| {
+                storage.queue_save();
+                None
+            }),
+        )
+        .expect("Couldn't connect to notify");
+    }
+
+    /// Saves once nothing changed for a moment.
+    fn queue_save(self: &Rc<Self>) {
+        if let Some(source) = self.save_source.borrow_mut().take() {
+            glib::source_remove(source);
+        }
+
+        let source = glib::timeout_add_local(
+            SAVE_DELAY_MS,
+            clone!(@weak self as storage => @default-return glib::Continue(false), move || {
+                storage.save_source.replace(None);
+                storage.save();
+                glib::Continue(false)
+            }),
+        );
+        self.save_source.replace(Some(source));
+    }
+
+    /// Saves right away if there are changes waiting to be saved, like before quitting.
+    pub fn flush(&self) {
+        if let Some(source) = self.save_source.borrow_mut().take() {
+            glib::source_remove(source);
+            self.save();
+        }
+    }
+
+    fn save(&self) {
+        if let Some(dir) = self.path.parent() {
+            if let Err(err) = fs::create_dir_all(dir) {
+                self.show_error(&format!(
+                    "The list couldn't be saved, {} couldn't be created: {}",
+                    dir.display(),
+                    err
+                ));
+                return;
+            }
+        }
+
+        let contents = match rows_to_json(&self.model) {
+            Ok(contents) => contents,
+            Err(err) => {
+                self.show_error(&format!("The list couldn't be saved: {}", err));
+                return;
+            }
+        };
+        let file = gio::File::new_for_path(&self.path);
+        if let Err(err) = file.replace_contents(
+            contents.as_bytes(),
+            None,
+            true,
+            gio::FileCreateFlags::NONE,
+            None::<&gio::Cancellable>,
+        ) {
+            self.show_error(&format!(
+                "The list couldn't be saved to {}: {}",
+                self.path.display(),
+                err
+            ));
+        }
+    }
+}
+
+/// Where `replace_contents` keeps the previous version of `path`.
+fn backup_path(path: &Path) -> PathBuf {
+    let mut backup = path.to_owned().into_os_string();
+    backup.push("~");
+    PathBuf::from(backup)
+}
+
+/// Reads the rows saved in `path`, if there is such a file.
+fn read_rows(path: &Path) -> Result<Option<Vec<RowData>>, String> {
+    let file = gio::File::new_for_path(path);
+    let contents = match file.load_contents(None::<&gio::Cancellable>) {
+        Ok((contents, _)) => contents,
+        Err(ref err) if err.kind::<gio::IOErrorEnum>() == Some(gio::IOErrorEnum::NotFound) => {
+            return Ok(None);
+        }
+        Err(err) => return Err(err.to_string()),
+    };
+
+    let text = str::from_utf8(&contents).map_err(|err| err.to_string())?;
+    rows_from_json(text).map(Some)
+}
+
+/// The whole file. The rows are only read once the version is known to be one we can read.
+#[derive(Serialize, Deserialize)]
+struct SavedList<R> {
+    version: u32,
+    rows: Vec<R>,
+}
+
+/// A row as it's saved. Everything but the name and count is missing in version 1.
+#[derive(Serialize, Deserialize)]
+struct SavedRow {
+    name: String,
+    count: u32,
+    #[serde(default)]
+    date: Option<String>,
+    #[serde(default)]
+    category: Option<String>,
+    #[serde(default)]
+    flagged: Option<bool>,
+    #[serde(default)]
+    color: Option<String>,
+}
+
+fn rows_to_json(model: &gio::ListStore) -> Result<String, serde_json::Error> {
+    let rows = (0..model.get_n_items())
+        .filter_map(|position| model.get_object(position))
+        .filter_map(|item| item.downcast::<RowData>().ok())
+        .map(|row| SavedRow {
+            name: row.get_name(),
+            count: row.get_count(),
+            date: row.get_date().map(|date| row_data::format_date(&date)),
+            category: Some(row.get_category().nick().to_owned()),
+            flagged: Some(row.get_flagged()),
+            color: Some(row.get_color().to_string()),
+        })
+        .collect();
+
+    serde_json::to_string_pretty(&SavedList {
+        version: FORMAT_VERSION,
+        rows,
+    })
+}
+
+fn rows_from_json(text: &str) -> Result<Vec<RowData>, String> {
+    let list = serde_json::from_str::<SavedList<serde_json::Value>>(text)
+        .map_err(|err| err.to_string())?;
+    if list.version > FORMAT_VERSION {
+        return Err(format!("Unknown format version {}", list.version));
+    }
+
+    list.rows
+        .into_iter()
+        .enumerate()
+        .map(|(i, row)| {
+            serde_json::from_value(row)
+                .map_err(|err| err.to_string())
+                .and_then(row_from_saved)
+                .map_err(|err| format!("Row {}: {}", i + 1, err))
+        })
+        .collect()
+}
+
+fn row_from_saved(saved: SavedRow) -> Result<RowData, String> {
+    if saved.count > 100 {
+        return Err(String::from("Count isn't a whole number from 0 to 100"));
+    }
+    let row = RowData::new(&saved.name, saved.count);
+
+    if let Some(date) = saved.date {
+        let date = row_data::parse_date(&date)
+            .ok_or_else(|| String::from("Date isn't written as YYYY-MM-DD"))?;
+        row.set_property("date", &date).expect("Couldn't set date");
+    }
+    if let Some(category) = saved.category {
+        let category =
+            Category::from_nick(&category).ok_or_else(|| String::from("Unknown category"))?;
+        row.set_property("category", &category)
+            .expect("Couldn't set category");
+    }
+    if let Some(flagged) = saved.flagged {
+        row.set_property("flagged", &flagged)
+            .expect("Couldn't set flagged");
+    }
+    if let Some(color) = saved.color {
+        let color = color
+            .parse::<gdk::RGBA>()
+            .map_err(|_| String::from("Invalid color"))?;
+        row.set_property("color", &color)
+            .expect("Couldn't set color");
+    }
+
+    Ok(row)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use row_data::DEFAULT_COLOR;
+
+    #[test]
+    fn version_1_rows_get_defaults() {
+        let rows = rows_from_json(r#"{"version": 1, "rows": [{"name": "a", "count": 3}]}"#)
+            .expect("Version 1 can be read");
+
+        assert_eq!(rows.len(), 1);
+        assert_eq!(rows[0].get_name(), "a");
+        assert_eq!(rows[0].get_count(), 3);
+        assert!(rows[0].get_date().is_none());
+        assert_eq!(rows[0].get_category(), Category::Other);
+        assert!(!rows[0].get_flagged());
+        assert_eq!(rows[0].get_color().to_string(), DEFAULT_COLOR.to_string());
+    }
+
+    #[test]
+    fn newer_versions_are_rejected() {
+        let text = format!(r#"{{"version": {}, "rows": []}}"#, FORMAT_VERSION + 1);
+        assert_eq!(
+            rows_from_json(&text).err(),
+            Some(format!("Unknown format version {}", FORMAT_VERSION + 1))
+        );
+    }
+
+    #[test]
+    fn bad_rows_are_reported_by_their_number() {
+        let text = r#"{"version": 2, "rows": [
+            {"name": "a", "count": 1},
+            {"name": "b", "count": 101}
+        ]}"#;
+        assert_eq!(
+            rows_from_json(text).err().as_deref(),
+            Some("Row 2: Count isn't a whole number from 0 to 100")
+        );
+
+        let text = r#"{"version": 2, "rows": [{"name": "a", "count": 1, "date": "1.2.2020"}]}"#;
+        assert_eq!(
+            rows_from_json(text).err().as_deref(),
+            Some("Row 1: Date isn't written as YYYY-MM-DD")
+        );
+
+        let text = r#"{"version": 2, "rows": [{"name": "a"}]}"#;
+        let err = rows_from_json(text).err().unwrap_or_default();
+        assert!(err.starts_with("Row 1: missing field `count`"));
+    }
+
+    #[test]
+    fn files_that_are_not_a_saved_list_are_rejected() {
+        assert!(rows_from_json("").is_err());
+        assert!(rows_from_json("[]").is_err());
+        assert!(rows_from_json(r#"{"rows": []}"#).is_err());
+    }
+
+    #[test]
+    fn saved_rows_are_read_back_the_same() {
+        let model = gio::ListStore::new(RowData::static_type());
+        let item = RowData::new("b", 100);
+        item.set_property("date", &row_data::parse_date("1999-12-31"))
+            .expect("Couldn't set date");
+        item.set_property("category", &Category::Work)
+            .expect("Couldn't set category");
+        item.set_property("flagged", &true)
+            .expect("Couldn't set flagged");
+        model.append(&item);
+
+        let text = rows_to_json(&model).expect("The rows can be written");
+        let rows = rows_from_json(&text).expect("The rows can be read back");
+
+        assert_eq!(rows.len(), 1);
+        assert_eq!(rows[0].get_name(), "b");
+        assert_eq!(rows[0].get_count(), 100);
+        assert_eq!(
+            rows[0].get_date().map(|date| row_data::format_date(&date)),
+            Some(String::from("1999-12-31"))
+        );
+        assert_eq!(rows[0].get_category(), Category::Work);
+        assert!(rows[0].get_flagged());
+    }
+}
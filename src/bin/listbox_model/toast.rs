@@ -0,0 +1,79 @@
+//! A short message at the bottom of the list offering to undo what just happened.
+
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use glib;
+use gtk;
+use gtk::prelude::*;
+
+// How long the message stays, unless it's dismissed
+const TIMEOUT_MS: u32 = 5000;
+
+pub struct Toast {
+    pub revealer: gtk::Revealer,
+    label: gtk::Label,
+    hide_source: RefCell<Option<glib::SourceId>>,
+}
+
+impl Toast {
+    pub fn new() -> Rc<Self> {
+        let revealer = gtk::Revealer::new();
+        revealer.set_halign(gtk::Align::Center);
+        revealer.set_valign(gtk::Align::End);
+        revealer.set_transition_type(gtk::RevealerTransitionType::SlideUp);
+
+        let container = gtk::Box::new(gtk::Orientation::Horizontal, 10);
+        container.get_style_context().add_class("app-notification");
+
+        let label = gtk::Label::new(None);
+        container.pack_start(&label, true, true, 0);
+
+        let undo_button = gtk::Button::with_label("Undo");
+        undo_button.set_action_name(Some("win.undo"));
+        container.pack_start(&undo_button, false, false, 0);
+
+        let close_button =
+            gtk::Button::from_icon_name(Some("window-close-symbolic"), gtk::IconSize::Button);
+        close_button.set_relief(gtk::ReliefStyle::None);
+        container.pack_start(&close_button, false, false, 0);
+
+        revealer.add(&container);
+
+        let toast = Rc::new(Toast {
+            revealer,
+            label,
+            hide_source: RefCell::new(None),
+        });
+
+        undo_button.connect_clicked(clone!(@weak toast => move |_| toast.hide()));
+        close_button.connect_clicked(clone!(@weak toast => move |_| toast.hide()));
+        toast
+    }
+
+    /// Shows `message` for a few seconds.
+    pub fn show(self: &Rc<Self>, message: &str) {
+        self.label.set_text(message);
+        self.revealer.set_reveal_child(true);
+
+        if let Some(source) = self.hide_source.borrow_mut().take() {
+            glib::source_remove(source);
+        }
+        let source = glib::timeout_add_local(
+            TIMEOUT_MS,
+            clone!(@weak self as toast => @default-return glib::Continue(false), move || {
+                toast.hide_source.replace(None);
+                toast.revealer.set_reveal_child(false);
+                glib::Continue(false)
+            }),
+        );
+        self.hide_source.replace(Some(source));
+    }
+
+    pub fn hide(&self) {
+        if let Some(source) = self.hide_source.borrow_mut().take() {
+            glib::source_remove(source);
+        }
+        self.revealer.set_reveal_child(false);
+    }
+}
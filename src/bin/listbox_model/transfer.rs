@@ -0,0 +1,595 @@
+//! Importing rows from and exporting them to CSV files, to exchange them with spreadsheets.
+//!
+//! The first line names the columns: `name` and `count` are required, while `date`,
+//! `category`, `flagged` and `color` can be left out and are filled with defaults then. Before
+//! anything is imported, a preview shows which lines can't be, and why.
+
+use std::collections::HashSet;
+use std::path::Path;
+use std::rc::Rc;
+
+use csv;
+use gdk;
+use gio;
+use gio::prelude::*;
+use glib;
+use gtk;
+use gtk::prelude::*;
+use pango;
+
+use editor;
+use row_data::{self, Category, RowData, DEFAULT_COLOR};
+
+const COLUMNS: [&str; 6] = ["name", "count", "date", "category", "flagged", "color"];
+
+/// Asks where to export the rows of `model` to and writes them there.
+pub fn export(window: &gtk::ApplicationWindow, model: &gio::ListStore) {
+    let file_chooser = gtk::FileChooserDialog::new(
+        Some("Export Rows"),
+        Some(window),
+        gtk::FileChooserAction::Save,
+    );
+    file_chooser.add_buttons(&[
+        ("Export", gtk::ResponseType::Ok),
+        ("Cancel", gtk::ResponseType::Cancel),
+    ]);
+    file_chooser.set_do_overwrite_confirmation(true);
+    file_chooser.set_current_name("rows.csv");
+    file_chooser.add_filter(&csv_filter());
+
+    file_chooser.connect_response(
+        clone!(@weak window, @weak model => move |file_chooser, response| {
+            let path = file_chooser.get_filename();
+            file_chooser.close();
+            if let (Some(path), gtk::ResponseType::Ok) = (path, response) {
+                if let Err(err) = write_csv(&path, &model) {
+                    show_error(&window, &format!("Couldn't export to {}", path.display()), &err);
+                }
+            }
+        }),
+    );
+
+    file_chooser.show_all();
+}
+
+/// Asks for a CSV file and shows what importing it into `model` would do.
+pub fn import(window: &gtk::ApplicationWindow, model: &gio::ListStore) {
+    let file_chooser = gtk::FileChooserDialog::new(
+        Some("Import Rows"),
+        Some(window),
+        gtk::FileChooserAction::Open,
+    );
+    file_chooser.add_buttons(&[
+        ("Open", gtk::ResponseType::Ok),
+        ("Cancel", gtk::ResponseType::Cancel),
+    ]);
+    file_chooser.add_filter(&csv_filter());
+
+    file_chooser.connect_response(
+        clone!(@weak window, @weak model => move |file_chooser, response| {
+            let path = file_chooser.get_filename();
+            file_chooser.close();
+            if let (Some(path), gtk::ResponseType::Ok) = (path, response) {
+                match read_csv(&path) {
+                    Ok(rows) => ImportDialog::new(&window, &model, &path, rows).show(),
+                    Err(err) => {
+                        show_error(&window, &format!("Couldn't import {}", path.display()), &err)
+                    }
+                }
+            }
+        }),
+    );
+
+    file_chooser.show_all();
+}
+
+fn csv_filter() -> gtk::FileFilter {
+    let filter = gtk::FileFilter::new();
+    filter.set_name(Some("CSV files"));
+    filter.add_mime_type("text/csv");
+    filter.add_pattern("*.csv");
+    filter
+}
+
+fn show_error(window: &gtk::ApplicationWindow, message: &str, details: &str) {
+    let dialog = gtk::MessageDialog::new(
+        Some(window),
+        gtk::DialogFlags::MODAL | gtk::DialogFlags::DESTROY_WITH_PARENT,
+        gtk::MessageType::Error,
+        gtk::ButtonsType::Close,
+        message,
+    );
+    dialog.set_property_secondary_text(Some(details));
+    dialog.connect_response(|dialog, _| dialog.close());
+    dialog.show_all();
+}
+
+fn write_csv(path: &Path, model: &gio::ListStore) -> Result<(), String> {
+    let out = rows_to_csv(model)?;
+
+    gio::File::new_for_path(path)
+        .replace_contents(
+            out.as_bytes(),
+            None,
+            false,
+            gio::FileCreateFlags::NONE,
+            None::<&gio::Cancellable>,
+        )
+        .map(|_| ())
+        .map_err(|err| err.to_string())
+}
+
+fn rows_to_csv(model: &gio::ListStore) -> Result<String, String> {
+    let mut writer = csv::WriterBuilder::new()
+        .terminator(csv::Terminator::CRLF)
+        .from_writer(Vec::new());
+    writer
+        .write_record(COLUMNS)
+        .map_err(|err| err.to_string())?;
+    for item in (0..model.get_n_items())
+        .filter_map(|position| model.get_object(position))
+        .filter_map(|item| item.downcast::<RowData>().ok())
+    {
+        let date = item
+            .get_date()
+            .map(|date| row_data::format_date(&date))
+            .unwrap_or_default();
+        writer
+            .write_record(&[
+                item.get_name(),
+                item.get_count().to_string(),
+                date,
+                item.get_category().nick().to_owned(),
+                item.get_flagged().to_string(),
+                item.get_color().to_string(),
+            ])
+            .map_err(|err| err.to_string())?;
+    }
+    let out = writer.into_inner().map_err(|err| err.to_string())?;
+    String::from_utf8(out).map_err(|err| err.to_string())
+}
+
+/// A line of the file to import, and the item made of it if it's valid.
+struct ImportRow {
+    line: usize,
+    /// The fields in the order of `COLUMNS`, as they were written in the file.
+    fields: Vec<String>,
+    item: Result<RowData, String>,
+}
+
+fn read_csv(path: &Path) -> Result<Vec<ImportRow>, String> {
+    let (contents, _) = gio::File::new_for_path(path)
+        .load_contents(None::<&gio::Cancellable>)
+        .map_err(|err| err.to_string())?;
+    let text =
+        String::from_utf8(contents).map_err(|_| String::from("The file isn't UTF-8 text"))?;
+    rows_from_csv(&text)
+}
+
+/// Reads the lines of a CSV file, with the problems of the ones that can't be imported.
+fn rows_from_csv(text: &str) -> Result<Vec<ImportRow>, String> {
+    // Lines with the wrong number of fields are still shown in the preview, with the problem
+    let mut reader = csv::ReaderBuilder::new()
+        .flexible(true)
+        .from_reader(text.as_bytes());
+
+    // Which field of each record goes into which column
+    let header = reader.headers().map_err(|err| err.to_string())?.clone();
+    if header.is_empty() {
+        return Err(String::from("The file is empty"));
+    }
+    let indices = COLUMNS
+        .iter()
+        .map(|column| {
+            header
+                .iter()
+                .position(|field| field.trim().eq_ignore_ascii_case(column))
+        })
+        .collect::<Vec<_>>();
+    if indices[0].is_none() || indices[1].is_none() {
+        return Err(String::from(
+            "The first line has to name the columns, with at least \"name\" and \"count\"",
+        ));
+    }
+
+    Ok(reader
+        .records()
+        .map(|record| {
+            let record = match record {
+                Ok(record) => record,
+                Err(err) => {
+                    return ImportRow {
+                        line: err.position().map_or(0, |position| line(text, position)),
+                        fields: vec![String::new(); COLUMNS.len()],
+                        item: Err(err.to_string()),
+                    }
+                }
+            };
+            let fields = indices
+                .iter()
+                .map(|index| {
+                    index
+                        .and_then(|index| record.get(index))
+                        .unwrap_or_default()
+                        .to_owned()
+                })
+                .collect::<Vec<_>>();
+            let item = if record.len() == header.len() {
+                item_from_fields(&fields)
+            } else {
+                Err(format!(
+                    "Expected {} fields, found {}",
+                    header.len(),
+                    record.len()
+                ))
+            };
+            ImportRow {
+                line: record.position().map_or(0, |position| line(text, position)),
+                fields,
+                item,
+            }
+        })
+        .collect())
+}
+
+/// The line of `text` the record at `position` starts on.
+///
+/// `csv` counts one line short after a CRLF, which is how the rows are exported, as it puts the
+/// start of the next record on the LF. A record can't start with a line break, so it's skipped.
+fn line(text: &str, position: &csv::Position) -> usize {
+    let mut start = position.byte() as usize;
+    if text[start..].starts_with('\n') {
+        start += 1;
+    }
+    text[..start].matches('\n').count() + 1
+}
+
+/// "1 row" or "n rows".
+fn rows(n: usize) -> String {
+    if n == 1 {
+        String::from("1 row")
+    } else {
+        format!("{} rows", n)
+    }
+}
+
+fn item_from_fields(fields: &[String]) -> Result<RowData, String> {
+    let field = |i: usize| fields[i].trim();
+
+    let count = match field(1).parse::<u32>() {
+        Ok(count) if count <= 100 => count,
+        _ => return Err(String::from("Count isn't a whole number from 0 to 100")),
+    };
+    let item = RowData::new(&fields[0], count);
+
+    if !field(2).is_empty() {
+        let date = row_data::parse_date(field(2))
+            .ok_or_else(|| String::from("Date isn't written as YYYY-MM-DD"))?;
+        item.set_property("date", &date).expect("Couldn't set date");
+    }
+
+    let category = match field(3) {
+        "" => Category::Other,
+        category => Category::ALL
+            .iter()
+            .cloned()
+            .find(|known| {
+                category.eq_ignore_ascii_case(known.nick())
+                    || category.eq_ignore_ascii_case(known.label())
+            })
+            .ok_or_else(|| format!("Unknown category \"{}\"", category))?,
+    };
+    item.set_property("category", &category)
+        .expect("Couldn't set category");
+
+    let flagged = match field(4).to_lowercase().as_str() {
+        "" | "false" | "no" | "0" => false,
+        "true" | "yes" | "1" => true,
+        _ => return Err(String::from("Flagged isn't true or false")),
+    };
+    item.set_property("flagged", &flagged)
+        .expect("Couldn't set flagged");
+
+    let color = match field(5) {
+        "" => DEFAULT_COLOR,
+        color => color
+            .parse::<gdk::RGBA>()
+            .map_err(|_| format!("Unknown color \"{}\"", color))?,
+    };
+    item.set_property("color", &color)
+        .expect("Couldn't set color");
+
+    Ok(item)
+}
+
+/// Shows the rows of a file before importing them, and whether they should be added to the
+/// existing ones or replace them.
+struct ImportDialog {
+    dialog: gtk::Dialog,
+    model: gio::ListStore,
+    rows: Vec<ImportRow>,
+    preview: gtk::ListStore,
+    summary: gtk::Label,
+    replace_button: gtk::RadioButton,
+}
+
+impl ImportDialog {
+    fn new(
+        window: &gtk::ApplicationWindow,
+        model: &gio::ListStore,
+        path: &Path,
+        rows: Vec<ImportRow>,
+    ) -> Rc<Self> {
+        let dialog = gtk::Dialog::with_buttons(
+            Some("Import Rows"),
+            Some(window),
+            gtk::DialogFlags::MODAL | gtk::DialogFlags::DESTROY_WITH_PARENT,
+            &[
+                ("Cancel", gtk::ResponseType::Cancel),
+                ("Import", gtk::ResponseType::Ok),
+            ],
+        );
+        dialog.set_default_size(640, 400);
+        dialog.set_default_response(gtk::ResponseType::Ok);
+
+        let container = gtk::Box::new(gtk::Orientation::Vertical, 6);
+        container.set_border_width(10);
+
+        let file_label = gtk::Label::new(Some(&path.display().to_string()));
+        file_label.set_xalign(0.0);
+        file_label.set_ellipsize(pango::EllipsizeMode::Middle);
+        container.pack_start(&file_label, false, false, 0);
+
+        // The line, the fields and what's wrong with them, all as text
+        let mut column_types = vec![glib::Type::U32];
+        column_types.extend(vec![glib::Type::String; COLUMNS.len() + 1]);
+        let preview = gtk::ListStore::new(&column_types);
+
+        let tree_view = gtk::TreeView::with_model(&preview);
+        let titles = [
+            "Line", "Name", "Count", "Date", "Category", "Flagged", "Color", "Problem",
+        ];
+        for (i, title) in titles.iter().enumerate() {
+            let renderer = gtk::CellRendererText::new();
+            let column = gtk::TreeViewColumn::new();
+            column.set_title(title);
+            column.set_resizable(true);
+            column.pack_start(&renderer, true);
+            column.add_attribute(&renderer, "text", i as i32);
+            tree_view.append_column(&column);
+        }
+
+        let scrolled_window = gtk::ScrolledWindow::new(gtk::NONE_ADJUSTMENT, gtk::NONE_ADJUSTMENT);
+        scrolled_window.set_shadow_type(gtk::ShadowType::EtchedIn);
+        scrolled_window.add(&tree_view);
+        container.pack_start(&scrolled_window, true, true, 0);
+
+        let summary = gtk::Label::new(None);
+        summary.set_xalign(0.0);
+        summary.set_line_wrap(true);
+        container.pack_start(&summary, false, false, 0);
+
+        let append_button = gtk::RadioButton::with_label("Add to the existing rows");
+        let replace_button =
+            gtk::RadioButton::with_label_from_widget(&append_button, "Replace the existing rows");
+        container.pack_start(&append_button, false, false, 0);
+        container.pack_start(&replace_button, false, false, 0);
+
+        dialog
+            .get_content_area()
+            .pack_start(&container, true, true, 0);
+
+        let import_dialog = Rc::new(ImportDialog {
+            dialog,
+            model: model.clone(),
+            rows,
+            preview,
+            summary,
+            replace_button,
+        });
+
+        // Whether a name is taken depends on whether the existing rows stay
+        import_dialog
+            .replace_button
+            .connect_toggled(clone!(@weak import_dialog => move |_| import_dialog.update()));
+        import_dialog.dialog.connect_response(
+            clone!(@strong import_dialog => move |dialog, response| {
+                if response == gtk::ResponseType::Ok {
+                    import_dialog.import();
+                }
+                dialog.close();
+            }),
+        );
+
+        import_dialog.update();
+        import_dialog
+    }
+
+    fn show(&self) {
+        self.dialog.show_all();
+    }
+
+    /// The items that can be imported, and for each line of the file what's wrong with it.
+    fn check(&self) -> (Vec<RowData>, Vec<Option<String>>) {
+        let mut taken = HashSet::new();
+        if !self.replace_button.get_active() {
+            taken.extend(
+                (0..self.model.get_n_items())
+                    .filter_map(|position| self.model.get_object(position))
+                    .filter_map(|item| item.downcast::<RowData>().ok())
+                    .map(|item| item.get_name().trim().to_owned()),
+            );
+        }
+
+        let mut items = Vec::new();
+        let mut problems = Vec::new();
+        for row in &self.rows {
+            let problem = match row.item {
+                Ok(ref item) => {
+                    let name = item.get_name();
+                    let result = editor::check_name(&name, |name| taken.contains(name));
+                    if result.is_ok() {
+                        taken.insert(name.trim().to_owned());
+                        items.push(item.clone());
+                    }
+                    result.err()
+                }
+                Err(ref err) => Some(err.clone()),
+            };
+            problems.push(problem);
+        }
+
+        (items, problems)
+    }
+
+    fn update(&self) {
+        let (items, problems) = self.check();
+
+        self.preview.clear();
+        for (row, problem) in self.rows.iter().zip(&problems) {
+            let line = row.line as u32;
+            let problem = problem.clone().unwrap_or_default();
+            let mut values: Vec<&dyn ToValue> = vec![&line];
+            values.extend(row.fields.iter().map(|field| field as &dyn ToValue));
+            values.push(&problem);
+
+            let columns = (0..values.len() as u32).collect::<Vec<_>>();
+            self.preview.set(&self.preview.append(), &columns, &values);
+        }
+
+        let skipped = self.rows.len() - items.len();
+        let summary = match (items.len(), skipped) {
+            (0, 0) => String::from("The file has no rows to import."),
+            (0, _) => String::from("None of the rows can be imported."),
+            (ok, 0) => format!("{} will be imported.", rows(ok)),
+            (ok, skipped) => format!(
+                "{} will be imported, {} skipped because of the problems listed.",
+                rows(ok),
+                rows(skipped)
+            ),
+        };
+        self.summary.set_text(&summary);
+        self.dialog
+            .set_response_sensitive(gtk::ResponseType::Ok, !items.is_empty());
+    }
+
+    fn import(&self) {
+        let (items, _) = self.check();
+        let items = items
+            .into_iter()
+            .map(|item| item.upcast::<glib::Object>())
+            .collect::<Vec<_>>();
+
+        // All in one go, so it can be undone at once
+        let n_items = self.model.get_n_items();
+        if self.replace_button.get_active() {
+            self.model.splice(0, n_items, &items);
+        } else {
+            self.model.splice(n_items, 0, &items);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn item(row: &ImportRow) -> RowData {
+        row.item.clone().expect("The row can be imported")
+    }
+
+    #[test]
+    fn quoted_fields_can_hold_commas_quotes_and_line_breaks() {
+        let text = "name,count\r\n\"Hello, \"\"world\"\"\nagain\",5\r\nnext,6\r\n";
+        let rows = rows_from_csv(text).expect("The file can be read");
+
+        assert_eq!(rows.len(), 2);
+        assert_eq!(rows[0].line, 2);
+        assert_eq!(item(&rows[0]).get_name(), "Hello, \"world\"\nagain");
+        assert_eq!(item(&rows[0]).get_count(), 5);
+        // The line break inside the quotes counts as well.
+        assert_eq!(rows[1].line, 4);
+        assert_eq!(item(&rows[1]).get_name(), "next");
+    }
+
+    #[test]
+    fn columns_are_found_by_their_name() {
+        let rows = rows_from_csv("Count , NAME,flagged\n3,b,yes\n").expect("The file can be read");
+
+        assert_eq!(rows.len(), 1);
+        assert_eq!(item(&rows[0]).get_name(), "b");
+        assert_eq!(item(&rows[0]).get_count(), 3);
+        assert!(item(&rows[0]).get_flagged());
+    }
+
+    #[test]
+    fn required_columns_have_to_be_there() {
+        match rows_from_csv("name,date\r\nx,2020-01-01\r\n") {
+            Ok(_) => panic!("Read rows without a count"),
+            Err(err) => assert!(err.contains("\"count\"")),
+        }
+        assert!(rows_from_csv("").is_err());
+    }
+
+    #[test]
+    fn lines_with_the_wrong_number_of_fields_are_reported() {
+        let rows = rows_from_csv("name,count\na,1\nb\nc,3,extra\n").expect("The file can be read");
+
+        assert_eq!(rows.len(), 3);
+        assert_eq!(rows[0].line, 2);
+        assert!(rows[0].item.is_ok());
+        assert_eq!(rows[1].line, 3);
+        assert_eq!(
+            rows[1].item.as_ref().err().map(String::as_str),
+            Some("Expected 2 fields, found 1")
+        );
+        // What could be read is still shown in the preview.
+        assert_eq!(rows[1].fields[0], "b");
+        assert_eq!(rows[2].line, 4);
+        assert_eq!(
+            rows[2].item.as_ref().err().map(String::as_str),
+            Some("Expected 2 fields, found 3")
+        );
+    }
+
+    #[test]
+    fn exported_rows_are_imported_the_same() {
+        let model = gio::ListStore::new(RowData::static_type());
+        model.append(&RowData::new("Plain", 0));
+        let item = RowData::new("Quoted, \"with\"\na line break", 100);
+        item.set_property("date", &row_data::parse_date("2020-02-29"))
+            .expect("Couldn't set date");
+        item.set_property("category", &Category::Shopping)
+            .expect("Couldn't set category");
+        item.set_property("flagged", &true)
+            .expect("Couldn't set flagged");
+        item.set_property("color", &"#3465a4".parse::<gdk::RGBA>().unwrap())
+            .expect("Couldn't set color");
+        model.append(&item);
+
+        let text = rows_to_csv(&model).expect("The rows can be written");
+        let rows = rows_from_csv(&text).expect("The rows can be read back");
+
+        assert_eq!(rows.len(), 2);
+        assert_eq!(rows[1].line, 3);
+        for (row, position) in rows.iter().zip(0..) {
+            let original = model
+                .get_object(position)
+                .and_then(|item| item.downcast::<RowData>().ok())
+                .expect("The model holds rows");
+            let imported = row.item.clone().expect("The row can be imported");
+            assert_eq!(imported.get_name(), original.get_name());
+            assert_eq!(imported.get_count(), original.get_count());
+            assert_eq!(
+                imported.get_date().map(|date| row_data::format_date(&date)),
+                original.get_date().map(|date| row_data::format_date(&date))
+            );
+            assert_eq!(imported.get_category(), original.get_category());
+            assert_eq!(imported.get_flagged(), original.get_flagged());
+            assert_eq!(
+                imported.get_color().to_string(),
+                original.get_color().to_string()
+            );
+        }
+    }
+}
@@ -0,0 +1,552 @@
+//! The work that can be queued up, each job running on a thread of its own.
+//!
+//! Jobs tell the UI how far they got with `Message`s sent over a channel, and check in with
+//! their `Control` between steps to pause or stop early.
+
+use std::ffi::{OsStr, OsString};
+use std::fs;
+use std::io::{self, Read, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Condvar, Mutex};
+use std::time::{Duration, Instant};
+
+use gio::prelude::*;
+
+// How much is read or written in one go.
+const CHUNK_SIZE: usize = 64 * 1024;
+// How often progress is reported at most. Any more often would just keep the UI busy.
+const REPORT_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Some work to do on a worker thread.
+pub trait Job: Send {
+    /// What the job does, like "Copy photos".
+    fn name(&self) -> String;
+
+    /// Does the work, reporting progress through `tx` and calling `control.checkpoint`
+    /// regularly. Returns something to show the user when done, if there is anything.
+    ///
+    /// A job stopping early because it was cancelled should return `Ok`, whatever it returns
+    /// then is ignored. Errors still get reported, like not being able to clean up after itself.
+    fn run(&self, control: &Control, tx: &glib::Sender<Message>) -> Result<Option<String>, String>;
+}
+
+/// What a worker thread tells the UI about its job.
+pub enum Message {
+    Progress(Progress),
+    Paused,
+    Resumed,
+    Completed(Option<String>),
+    Cancelled,
+    Failed(String),
+}
+
+/// How far along a job is.
+#[derive(Clone, Copy)]
+pub struct Progress {
+    pub done: u64,
+    /// `None` for as long as the job doesn't know yet.
+    pub total: Option<u64>,
+    pub unit: Unit,
+}
+
+/// What a job counts its progress in.
+#[derive(Clone, Copy)]
+pub enum Unit {
+    Items,
+    Bytes,
+    Percent,
+}
+
+impl Unit {
+    /// Something like "3 of 10 items" or "1.2 MB".
+    pub fn describe(self, done: u64, total: Option<u64>) -> String {
+        match (self, total) {
+            (Unit::Items, Some(total)) => format!("{} of {} items", done, total),
+            (Unit::Items, None) => format!("{} items", done),
+            (Unit::Bytes, Some(total)) => {
+                format!("{} of {}", format_size(done), format_size(total))
+            }
+            (Unit::Bytes, None) => format_size(done),
+            (Unit::Percent, Some(_)) => format!("{} %", done),
+            (Unit::Percent, None) => String::new(),
+        }
+    }
+
+    pub fn describe_rate(self, per_second: f64) -> String {
+        match self {
+            Unit::Items => format!("{:.1} items/s", per_second),
+            Unit::Bytes => format!("{}/s", format_size(per_second as u64)),
+            Unit::Percent => format!("{:.1} %/s", per_second),
+        }
+    }
+}
+
+fn format_size(size: u64) -> String {
+    glib::format_size(size)
+        .map(|size| size.to_string())
+        .unwrap_or_else(|| format!("{} bytes", size))
+}
+
+/// Lets the UI pause, resume and cancel a job running on another thread.
+///
+/// The worker has to check in with `checkpoint` every now and then, which is where it stops
+/// while paused and finds out that it was cancelled.
+#[derive(Default)]
+pub struct Control {
+    cancelled: AtomicBool,
+    paused: Mutex<bool>,
+    resumed: Condvar,
+    /// Cancelled along with the job, to get it out of blocking gio calls.
+    cancellable: gio::Cancellable,
+}
+
+impl Control {
+    pub fn is_paused(&self) -> bool {
+        *self.paused.lock().unwrap()
+    }
+
+    pub fn set_paused(&self, paused: bool) {
+        *self.paused.lock().unwrap() = paused;
+        self.resumed.notify_all();
+    }
+
+    pub fn cancel(&self) {
+        // Taken so a worker about to wait can't miss the notification.
+        let _paused = self.paused.lock().unwrap();
+        self.cancelled.store(true, Ordering::SeqCst);
+        self.cancellable.cancel();
+        self.resumed.notify_all();
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::SeqCst)
+    }
+
+    pub fn cancellable(&self) -> &gio::Cancellable {
+        &self.cancellable
+    }
+
+    /// Blocks for as long as the job is paused. Returns `false` once it should stop.
+    pub fn checkpoint(&self, tx: &glib::Sender<Message>) -> bool {
+        let mut paused = self.paused.lock().unwrap();
+        if *paused && !self.is_cancelled() {
+            let _ = tx.send(Message::Paused);
+            while *paused && !self.is_cancelled() {
+                paused = self.resumed.wait(paused).unwrap();
+            }
+            if !self.is_cancelled() {
+                let _ = tx.send(Message::Resumed);
+            }
+        }
+
+        !self.is_cancelled()
+    }
+}
+
+/// Sends progress over to the UI, leaving out updates that come too quickly after another.
+struct Reporter<'a> {
+    tx: &'a glib::Sender<Message>,
+    unit: Unit,
+    total: Option<u64>,
+    last: Option<Instant>,
+}
+
+impl<'a> Reporter<'a> {
+    fn new(tx: &'a glib::Sender<Message>, unit: Unit) -> Self {
+        Reporter {
+            tx,
+            unit,
+            total: None,
+            last: None,
+        }
+    }
+
+    fn report(&mut self, done: u64) {
+        match self.last {
+            Some(last) if last.elapsed() < REPORT_INTERVAL => (),
+            _ => self.report_now(done),
+        }
+    }
+
+    fn report_now(&mut self, done: u64) {
+        self.last = Some(Instant::now());
+        let _ = self.tx.send(Message::Progress(Progress {
+            done,
+            total: self.total,
+            unit: self.unit,
+        }));
+    }
+}
+
+/// Describes an I/O error along with the file it happened on.
+fn io_error(path: &Path, err: io::Error) -> String {
+    format!("{}: {}", path.display(), err)
+}
+
+fn file_name(path: &Path) -> String {
+    path.file_name()
+        .unwrap_or(path.as_os_str())
+        .to_string_lossy()
+        .into_owned()
+}
+
+/// Copies a directory with everything in it, counting the bytes copied.
+///
+/// Only files and directories are copied, anything else like symbolic links is left out. A copy
+/// that is cancelled or fails halfway through is removed again.
+pub struct CopyDirectory {
+    source: PathBuf,
+    into: PathBuf,
+    name: OsString,
+}
+
+impl CopyDirectory {
+    /// Copies `source` into the directory `into`, under the same name.
+    pub fn new(source: PathBuf, into: &Path) -> Self {
+        let name = source
+            .file_name()
+            .unwrap_or_else(|| OsStr::new("copy"))
+            .to_owned();
+        CopyDirectory {
+            source,
+            into: into.to_owned(),
+            name,
+        }
+    }
+
+    /// Lists everything below `dir` that's going to be copied, parents before their children,
+    /// and adds up the size of the files.
+    fn scan(
+        &self,
+        dir: &Path,
+        entries: &mut Vec<(PathBuf, bool)>,
+        total: &mut u64,
+        control: &Control,
+        tx: &glib::Sender<Message>,
+    ) -> Result<(), String> {
+        for entry in fs::read_dir(dir).map_err(|err| io_error(dir, err))? {
+            if !control.checkpoint(tx) {
+                return Ok(());
+            }
+
+            let entry = entry.map_err(|err| io_error(dir, err))?;
+            let path = entry.path();
+            let file_type = entry.file_type().map_err(|err| io_error(&path, err))?;
+            if file_type.is_dir() {
+                entries.push((path.clone(), true));
+                self.scan(&path, entries, total, control, tx)?;
+            } else if file_type.is_file() {
+                *total += entry.metadata().map_err(|err| io_error(&path, err))?.len();
+                entries.push((path, false));
+            }
+        }
+        Ok(())
+    }
+
+    /// Copies `entries` from `source` over to `destination`, which has to exist already.
+    fn copy_entries(
+        &self,
+        source: &Path,
+        destination: &Path,
+        entries: Vec<(PathBuf, bool)>,
+        reporter: &mut Reporter,
+        control: &Control,
+    ) -> Result<(), String> {
+        let mut done = 0;
+        for (path, is_dir) in entries {
+            if !control.checkpoint(reporter.tx) {
+                break;
+            }
+
+            let relative = path
+                .strip_prefix(source)
+                .expect("Entries are below the source");
+            let target = destination.join(relative);
+            if is_dir {
+                fs::create_dir(&target).map_err(|err| io_error(&target, err))?;
+            } else {
+                self.copy_file(&path, &target, &mut done, reporter, control)?;
+            }
+        }
+        reporter.report_now(done);
+        Ok(())
+    }
+
+    fn copy_file(
+        &self,
+        from: &Path,
+        to: &Path,
+        done: &mut u64,
+        reporter: &mut Reporter,
+        control: &Control,
+    ) -> Result<(), String> {
+        let mut reader = fs::File::open(from).map_err(|err| io_error(from, err))?;
+        let mut writer = fs::File::create(to).map_err(|err| io_error(to, err))?;
+        let mut buffer = vec![0; CHUNK_SIZE];
+        loop {
+            if !control.checkpoint(reporter.tx) {
+                return Ok(());
+            }
+
+            let read = reader
+                .read(&mut buffer)
+                .map_err(|err| io_error(from, err))?;
+            if read == 0 {
+                return Ok(());
+            }
+            writer
+                .write_all(&buffer[..read])
+                .map_err(|err| io_error(to, err))?;
+
+            *done += read as u64;
+            reporter.report(*done);
+        }
+    }
+}
+
+impl Job for CopyDirectory {
+    fn name(&self) -> String {
+        format!("Copy {}", file_name(&self.source))
+    }
+
+    fn run(&self, control: &Control, tx: &glib::Sender<Message>) -> Result<Option<String>, String> {
+        // Symbolic links and ".." could hide that the destination is inside the source.
+        let source = fs::canonicalize(&self.source).map_err(|err| io_error(&self.source, err))?;
+        let into = fs::canonicalize(&self.into).map_err(|err| io_error(&self.into, err))?;
+        let destination = into.join(&self.name);
+        if destination.exists() {
+            return Err(format!("{} already exists", destination.display()));
+        }
+        if destination.starts_with(&source) {
+            return Err(String::from("Can't copy a directory into itself"));
+        }
+
+        // Going through everything first can take a while, with nothing to show for it yet.
+        let mut reporter = Reporter::new(tx, Unit::Bytes);
+        reporter.report_now(0);
+        let mut entries = Vec::new();
+        let mut total = 0;
+        self.scan(&source, &mut entries, &mut total, control, tx)?;
+        if control.is_cancelled() {
+            return Ok(None);
+        }
+        reporter.total = Some(total);
+
+        fs::create_dir(&destination).map_err(|err| io_error(&destination, err))?;
+        let result = self.copy_entries(&source, &destination, entries, &mut reporter, control);
+        if result.is_err() || control.is_cancelled() {
+            // Half a copy is easily mistaken for the whole thing later on.
+            if let Err(err) = fs::remove_dir_all(&destination) {
+                let stopped = result.err().unwrap_or_else(|| String::from("Cancelled"));
+                return Err(format!(
+                    "{}, and the partial copy couldn't be removed: {}",
+                    stopped,
+                    io_error(&destination, err)
+                ));
+            }
+        }
+
+        result.map(|()| None)
+    }
+}
+
+/// Calculates the SHA-256 checksum of a file.
+pub struct HashFile {
+    path: PathBuf,
+}
+
+impl HashFile {
+    pub fn new(path: PathBuf) -> Self {
+        HashFile { path }
+    }
+}
+
+impl Job for HashFile {
+    fn name(&self) -> String {
+        format!("SHA-256 of {}", file_name(&self.path))
+    }
+
+    fn run(&self, control: &Control, tx: &glib::Sender<Message>) -> Result<Option<String>, String> {
+        let path = &self.path;
+        let mut file = fs::File::open(path).map_err(|err| io_error(path, err))?;
+        let mut reporter = Reporter::new(tx, Unit::Bytes);
+        reporter.total = Some(file.metadata().map_err(|err| io_error(path, err))?.len());
+
+        let mut checksum = glib::Checksum::new(glib::ChecksumType::Sha256);
+        let mut buffer = vec![0; CHUNK_SIZE];
+        let mut done = 0;
+        loop {
+            if !control.checkpoint(tx) {
+                return Ok(None);
+            }
+
+            let read = file.read(&mut buffer).map_err(|err| io_error(path, err))?;
+            if read == 0 {
+                break;
+            }
+            checksum.update(&buffer[..read]);
+
+            done += read as u64;
+            reporter.report(done);
+        }
+        reporter.report_now(done);
+
+        Ok(checksum.get_string())
+    }
+}
+
+/// Runs a command, picking up its progress from percentages like "42%" in its output.
+///
+/// Pausing stops reading the output, so the command itself only stops once nobody is taking
+/// what it writes anymore.
+pub struct RunCommand {
+    command_line: String,
+    argv: Vec<OsString>,
+}
+
+impl RunCommand {
+    /// Splits `command_line` into arguments the way a shell would.
+    pub fn new(command_line: &str) -> Result<Self, glib::Error> {
+        let argv = glib::shell_parse_argv(command_line)?;
+        Ok(RunCommand {
+            command_line: command_line.to_owned(),
+            argv,
+        })
+    }
+}
+
+impl Job for RunCommand {
+    fn name(&self) -> String {
+        self.command_line.clone()
+    }
+
+    fn run(&self, control: &Control, tx: &glib::Sender<Message>) -> Result<Option<String>, String> {
+        let argv: Vec<&OsStr> = self.argv.iter().map(OsString::as_os_str).collect();
+        // Progress often goes to stderr, which is where errors end up as well.
+        let flags = gio::SubprocessFlags::STDOUT_PIPE | gio::SubprocessFlags::STDERR_MERGE;
+        let subprocess = gio::Subprocess::newv(&argv, flags).map_err(|err| err.to_string())?;
+        let output = subprocess
+            .get_stdout_pipe()
+            .expect("Couldn't get stdout pipe");
+
+        // The bar pulses until the first percentage shows up.
+        let mut reporter = Reporter::new(tx, Unit::Percent);
+        reporter.report_now(0);
+
+        let mut buffer = vec![0; CHUNK_SIZE];
+        let mut line = Vec::new();
+        let mut last_line = String::new();
+        loop {
+            if !control.checkpoint(tx) {
+                subprocess.force_exit();
+                return Ok(None);
+            }
+
+            let read = match output.read(&mut buffer, Some(control.cancellable())) {
+                Ok(read) => read,
+                Err(_) if control.is_cancelled() => {
+                    subprocess.force_exit();
+                    return Ok(None);
+                }
+                Err(err) => return Err(err.to_string()),
+            };
+            if read == 0 {
+                break;
+            }
+
+            // Progress bars tend to redraw themselves with a carriage return rather than
+            // starting a new line.
+            for &byte in &buffer[..read] {
+                if byte != b'\n' && byte != b'\r' {
+                    line.push(byte);
+                    continue;
+                }
+                if line.is_empty() {
+                    continue;
+                }
+
+                last_line = String::from_utf8_lossy(&line).into_owned();
+                line.clear();
+                if let Some(percent) = parse_percentage(&last_line) {
+                    reporter.total = Some(100);
+                    reporter.report(percent);
+                }
+            }
+        }
+
+        match subprocess.wait(Some(control.cancellable())) {
+            Ok(()) => (),
+            Err(_) if control.is_cancelled() => {
+                subprocess.force_exit();
+                return Ok(None);
+            }
+            Err(err) => return Err(err.to_string()),
+        }
+        if subprocess.get_successful() {
+            Ok(None)
+        } else if subprocess.get_if_exited() {
+            Err(format!(
+                "exited with status {}: {}",
+                subprocess.get_exit_status(),
+                last_line
+            ))
+        } else {
+            Err(format!("terminated: {}", last_line))
+        }
+    }
+}
+
+/// Finds the first percentage in `line`, like the 42 in "Downloading... 42.5%".
+fn parse_percentage(line: &str) -> Option<u64> {
+    line.match_indices('%').find_map(|(end, _)| {
+        let number = line[..end].trim_end();
+        let start = number
+            .rfind(|c: char| !(c.is_ascii_digit() || c == '.'))
+            .map_or(0, |i| i + 1);
+        number[start..]
+            .trim_start_matches('.')
+            .parse::<f64>()
+            .ok()
+            .filter(|percent| (0.0..=100.0).contains(percent))
+            .map(|percent| percent as u64)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn percentages_are_found() {
+        assert_eq!(parse_percentage("42%"), Some(42));
+        assert_eq!(parse_percentage("Downloading... 42.5%"), Some(42));
+        assert_eq!(parse_percentage("Progress...7 %"), Some(7));
+        assert_eq!(parse_percentage("100% done"), Some(100));
+        assert_eq!(
+            parse_percentage("  1650K .......... .......... 50% 1.2M 3s"),
+            Some(50)
+        );
+    }
+
+    #[test]
+    fn first_percentage_wins() {
+        assert_eq!(
+            parse_percentage("12% of 3 files, 80% of the bytes"),
+            Some(12)
+        );
+    }
+
+    #[test]
+    fn percentages_out_of_range_are_skipped() {
+        assert_eq!(parse_percentage("150% then 20%"), Some(20));
+    }
+
+    #[test]
+    fn lines_without_percentages_are_ignored() {
+        assert_eq!(parse_percentage("Resolving example.com"), None);
+        assert_eq!(parse_percentage("%"), None);
+        assert_eq!(parse_percentage("100 percent"), None);
+        assert_eq!(parse_percentage("1.2.3%"), None);
+    }
+}
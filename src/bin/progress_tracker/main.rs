@@ -0,0 +1,1112 @@
+//! Track progress with background threads and channels.
+//!
+//! Every click on "add job" queues up another job of the kind picked next to it: copying a
+//! directory, calculating the SHA-256 checksum of a file or running a command. Each job is shown
+//! as a row with its own progress bar. Only as many jobs as set next to the button run at once,
+//! the others wait for one of them to finish. Finished jobs move over to the history page.
+//!
+//! New kinds of work can be added by implementing the `Job` trait in `jobs.rs`.
+//!
+//! Running jobs can be paused, resumed and cancelled. Their threads check in with a shared
+//! `Control` between steps, which is also where they sleep while paused. A job that is cancelled
+//! or fails gets a view of its own for a moment, and so does the queue once it ran dry. While
+//! every running job is paused, a view saying so offers to resume them all.
+//!
+//! Jobs report how many items, bytes or percent they got through out of how many, from which the rows
+//! show the throughput and the time left. Until a job knows its total, its bar just pulses.
+//!
+//! When a job finishes while the window isn't focused, a desktop notification is sent and the
+//! window asks for attention. How long "All jobs done" is shown can be set in the header bar.
+
+use gio::prelude::*;
+use glib::clone;
+use gtk::prelude::*;
+
+use std::cell::{Cell, RefCell};
+use std::collections::VecDeque;
+use std::env::args;
+use std::path::PathBuf;
+use std::rc::Rc;
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+mod jobs;
+
+use crate::jobs::{Control, CopyDirectory, HashFile, Job, Message, Progress, RunCommand};
+
+pub fn main() {
+    glib::set_program_name(Some("Progress Tracker"));
+
+    let application = gtk::Application::new(
+        Some("com.github.progress-tracker"),
+        gio::ApplicationFlags::empty(),
+    )
+    .expect("initialization failed");
+
+    application.connect_startup(|app| {
+        let application = Application::new(app);
+
+        let application_container = RefCell::new(Some(application));
+        app.connect_shutdown(move |_| {
+            let application = application_container
+                .borrow_mut()
+                .take()
+                .expect("Shutdown called multiple times");
+            // Here we could do whatever we need to do for shutdown now
+            drop(application);
+        });
+    });
+
+    application.connect_activate(|_| {});
+    application.run(&args().collect::<Vec<_>>());
+}
+
+pub struct Application {
+    pub widgets: Rc<Widgets>,
+    pub queue: Rc<Queue>,
+}
+
+impl Application {
+    pub fn new(application: &gtk::Application) -> Self {
+        let widgets = Rc::new(Widgets::new(application));
+        let app = Application {
+            queue: Queue::new(&widgets),
+            widgets,
+        };
+
+        app.connect_queue();
+        app.connect_notifications(application);
+
+        app
+    }
+
+    fn connect_queue(&self) {
+        self.widgets.main_view.button.connect_clicked(
+            clone!(@weak self.widgets as widgets, @weak self.queue as queue => move |_| {
+                widgets.choose_job(clone!(@weak queue => move |job| queue.add(job)));
+            }),
+        );
+
+        // A higher limit can start some of the waiting jobs right away. Lowering it lets the
+        // running ones finish first.
+        self.widgets.main_view.limit.connect_value_changed(
+            clone!(@weak self.queue as queue => move |_| {
+                queue.start_waiting();
+            }),
+        );
+
+        self.widgets.paused_view.resume_button.connect_clicked(
+            clone!(@weak self.queue as queue => move |_| {
+                queue.resume_all();
+            }),
+        );
+        self.widgets.paused_view.queue_button.connect_clicked(
+            clone!(@weak self.widgets as widgets => move |_| {
+                widgets.view_stack.set_visible_child_name("queue");
+            }),
+        );
+
+        // Nobody would be left to see how the jobs end, and their threads would keep going.
+        self.widgets.window.connect_delete_event(
+            clone!(@weak self.queue as queue => @default-return Inhibit(false), move |_, _| {
+                queue.cancel_all();
+                Inhibit(false)
+            }),
+        );
+    }
+
+    /// Adds the actions behind the buttons of the notifications.
+    fn connect_notifications(&self, application: &gtk::Application) {
+        let open_history = gio::SimpleAction::new("open-history", None);
+        open_history.connect_activate(clone!(@weak self.widgets as widgets => move |_, _| {
+            widgets.view_stack.set_visible_child_name("history");
+            widgets.window.present();
+            widgets.withdraw_notification();
+        }));
+
+        let dismiss = gio::SimpleAction::new("dismiss", None);
+        dismiss.connect_activate(clone!(@weak self.widgets as widgets => move |_, _| {
+            widgets.withdraw_notification();
+        }));
+
+        application.add_action(&open_history);
+        application.add_action(&dismiss);
+
+        // Once the user is back, whatever finished in the meantime is right in front of them.
+        self.widgets.window.connect_property_is_active_notify(
+            clone!(@weak self.widgets as widgets => move |window| {
+                if window.is_active() {
+                    widgets.withdraw_notification();
+                }
+            }),
+        );
+    }
+}
+
+/// A job in the queue, from waiting for its turn until it ended up in the history.
+pub struct QueuedJob {
+    name: String,
+    /// Handed over to the worker thread once the job starts.
+    work: RefCell<Option<Box<dyn Job>>>,
+    row: JobRow,
+    control: Arc<Control>,
+    progress: Cell<Option<Progress>>,
+    throughput: RefCell<Throughput>,
+    pulsing: Cell<bool>,
+    /// Whether the worker said it's paused, rather than just being asked to.
+    paused: Cell<bool>,
+}
+
+impl QueuedJob {
+    fn show_progress(self: &Rc<Self>, progress: Progress) {
+        self.progress.set(Some(progress));
+        let mut throughput = self.throughput.borrow_mut();
+        throughput.update(progress.done);
+
+        let mut parts = vec![progress.unit.describe(progress.done, progress.total)];
+        if let Some(rate) = throughput.rate {
+            parts.push(progress.unit.describe_rate(rate));
+        }
+        match progress.total {
+            Some(total) => {
+                self.pulsing.set(false);
+                let fraction = if total == 0 {
+                    1.0
+                } else {
+                    progress.done as f64 / total as f64
+                };
+                self.row.progress.set_fraction(fraction.min(1.0));
+                if let Some(left) = throughput.time_left(progress.done, total) {
+                    parts.push(format!("{} left", format_duration(left)));
+                }
+            }
+            None => self.start_pulsing(),
+        }
+        parts.retain(|part| !part.is_empty());
+        self.row.progress.set_text(Some(&parts.join(" · ")));
+        self.row.progress.set_show_text(true);
+    }
+
+    /// Keeps the bar going back and forth until the job knows how much there is to do.
+    fn start_pulsing(self: &Rc<Self>) {
+        if self.pulsing.replace(true) {
+            return;
+        }
+
+        glib::timeout_add_local(
+            100,
+            clone!(@weak self as job => @default-return glib::Continue(false), move || {
+                if job.pulsing.get() {
+                    job.row.progress.pulse();
+                }
+                glib::Continue(job.pulsing.get())
+            }),
+        );
+    }
+
+    /// Leaves the bar full, with just how much got done, once the job completed. What the job
+    /// came up with goes where the status was, so it can be copied from there.
+    fn show_completed(&self, result: Option<String>) {
+        self.row.progress.set_fraction(1.0);
+        if let Some(progress) = self.progress.get() {
+            let text = progress.unit.describe(progress.done, progress.total);
+            self.row.progress.set_text(Some(&text));
+        }
+
+        match result {
+            Some(result) => {
+                self.row.status.set_text(&result);
+                self.row.status.set_tooltip_text(Some(&result));
+                self.row.status.set_selectable(true);
+            }
+            None => self.row.status.set_text("Completed"),
+        }
+    }
+}
+
+/// Runs the jobs added to it, no more at a time than the limit set in the main view.
+pub struct Queue {
+    widgets: Rc<Widgets>,
+    waiting: RefCell<VecDeque<Rc<QueuedJob>>>,
+    running: RefCell<Vec<Rc<QueuedJob>>>,
+}
+
+impl Queue {
+    pub fn new(widgets: &Rc<Widgets>) -> Rc<Self> {
+        Rc::new(Queue {
+            widgets: widgets.clone(),
+            waiting: RefCell::new(VecDeque::new()),
+            running: RefCell::new(Vec::new()),
+        })
+    }
+
+    /// Queues up a new job, which starts right away if there's a free slot.
+    pub fn add(self: &Rc<Self>, work: Box<dyn Job>) {
+        let name = work.name();
+        let job = Rc::new(QueuedJob {
+            row: JobRow::new(&name),
+            name,
+            work: RefCell::new(Some(work)),
+            control: Arc::new(Control::default()),
+            progress: Cell::new(None),
+            throughput: RefCell::new(Throughput::default()),
+            pulsing: Cell::new(false),
+            paused: Cell::new(false),
+        });
+
+        job.row
+            .pause_button
+            .connect_clicked(clone!(@weak job => move |button| {
+                // Only takes effect once the worker gets around to it, see `Message::Paused`.
+                button.set_sensitive(false);
+                job.control.set_paused(!job.control.is_paused());
+            }));
+        job.row.cancel_button.connect_clicked(
+            clone!(@weak self as queue, @weak job => move |button| {
+                button.set_sensitive(false);
+                queue.cancel(&job);
+            }),
+        );
+
+        self.widgets.main_view.list_box.add(&job.row.container);
+        job.row.container.show_all();
+
+        self.waiting.borrow_mut().push_back(job);
+        self.start_waiting();
+    }
+
+    /// Starts waiting jobs for as long as there are free slots.
+    pub fn start_waiting(self: &Rc<Self>) {
+        let limit = self.widgets.main_view.limit.get_value_as_int() as usize;
+        while self.running.borrow().len() < limit {
+            let job = match self.waiting.borrow_mut().pop_front() {
+                Some(job) => job,
+                None => break,
+            };
+            self.start(job);
+        }
+    }
+
+    fn start(self: &Rc<Self>, job: Rc<QueuedJob>) {
+        self.running.borrow_mut().push(job.clone());
+        job.row.status.set_text("Running");
+        job.row.pause_button.set_sensitive(true);
+
+        let work = job.work.borrow_mut().take().expect("Job started twice");
+        let control = job.control.clone();
+        let (tx, rx) = glib::MainContext::channel(glib::PRIORITY_DEFAULT);
+        thread::spawn(move || {
+            let message = match work.run(&control, &tx) {
+                Err(err) => Message::Failed(err),
+                Ok(_) if control.is_cancelled() => Message::Cancelled,
+                Ok(result) => Message::Completed(result),
+            };
+            let _ = tx.send(message);
+        });
+
+        rx.attach(
+            None,
+            clone!(@weak self as queue => @default-return glib::Continue(false), move |message| {
+                let row = &job.row;
+                match message {
+                    Message::Progress(progress) => {
+                        job.show_progress(progress);
+                        return glib::Continue(true);
+                    }
+                    Message::Paused => {
+                        job.pulsing.set(false);
+                        job.paused.set(true);
+                        row.status.set_text("Paused");
+                        row.pause_button.set_label("resume");
+                        row.pause_button.set_sensitive(true);
+                        queue.update_paused_view();
+                        return glib::Continue(true);
+                    }
+                    Message::Resumed => {
+                        job.throughput.borrow_mut().restart();
+                        job.paused.set(false);
+                        row.status.set_text("Running");
+                        row.pause_button.set_label("pause");
+                        row.pause_button.set_sensitive(true);
+                        queue.update_paused_view();
+                        return glib::Continue(true);
+                    }
+                    Message::Completed(result) => {
+                        job.show_completed(result);
+                        queue.widgets.notify(&format!("{} completed", job.name));
+                    }
+                    Message::Cancelled => {
+                        row.status.set_text("Cancelled");
+                        queue.widgets.show_cancelled(&job.name);
+                    }
+                    Message::Failed(err) => {
+                        row.status.set_text(&format!("Failed: {}", err));
+                        queue.widgets.show_failed(&job.name, &err);
+                        queue.widgets.notify(&format!("{} failed: {}", job.name, err));
+                    }
+                }
+
+                job.pulsing.set(false);
+                queue
+                    .running
+                    .borrow_mut()
+                    .retain(|running| !Rc::ptr_eq(running, &job));
+                queue.finish(&job);
+                queue.start_waiting();
+                queue.update_paused_view();
+                glib::Continue(false)
+            }),
+        );
+    }
+
+    fn cancel(&self, job: &Rc<QueuedJob>) {
+        job.control.cancel();
+
+        // A running job reports back once its thread stopped, a waiting one never got that far.
+        let position = self
+            .waiting
+            .borrow()
+            .iter()
+            .position(|waiting| Rc::ptr_eq(waiting, job));
+        if let Some(position) = position {
+            self.waiting.borrow_mut().remove(position);
+            job.row.status.set_text("Cancelled");
+            self.widgets.show_cancelled(&job.name);
+            self.finish(job);
+        }
+    }
+
+    /// Cancels every job, the running ones as well as those still waiting for their turn.
+    pub fn cancel_all(&self) {
+        let waiting = self.waiting.borrow_mut().drain(..).collect::<Vec<_>>();
+        for job in self.running.borrow().iter().chain(&waiting) {
+            job.control.cancel();
+        }
+    }
+
+    /// Resumes every running job that's paused.
+    fn resume_all(&self) {
+        for job in self.running.borrow().iter() {
+            if job.control.is_paused() {
+                job.row.pause_button.set_sensitive(false);
+                job.control.set_paused(false);
+            }
+        }
+    }
+
+    /// Shows the paused view once every running job is paused, and the queue again once that's
+    /// no longer the case.
+    fn update_paused_view(&self) {
+        let running = self.running.borrow();
+        let all_paused = !running.is_empty() && running.iter().all(|job| job.paused.get());
+        if all_paused {
+            self.widgets.show_paused(running.len());
+        } else {
+            self.widgets.hide_paused();
+        }
+    }
+
+    /// Moves the row of a job that ended over to the history, newest first.
+    fn finish(&self, job: &QueuedJob) {
+        job.row.buttons.hide();
+        self.widgets.main_view.list_box.remove(&job.row.container);
+        self.widgets
+            .history_view
+            .list_box
+            .insert(&job.row.container, 0);
+
+        if self.running.borrow().is_empty() && self.waiting.borrow().is_empty() {
+            self.widgets.show_complete();
+        }
+    }
+}
+
+fn format_duration(duration: Duration) -> String {
+    let seconds = duration.as_secs();
+    if seconds < 60 {
+        format!("{} s", seconds)
+    } else if seconds < 60 * 60 {
+        format!("{} min {} s", seconds / 60, seconds % 60)
+    } else {
+        format!("{} h {} min", seconds / 3600, seconds / 60 % 60)
+    }
+}
+
+// How much each new measurement counts against the ones before, between 0 and 1. Lower values
+// give a steadier estimate that takes longer to catch up with changes.
+const SMOOTHING: f64 = 0.3;
+
+/// Measures how fast a job gets along, smoothed over time so the estimate doesn't jump around.
+#[derive(Default)]
+struct Throughput {
+    last: Option<(Instant, u64)>,
+    /// Units per second.
+    rate: Option<f64>,
+}
+
+impl Throughput {
+    fn update(&mut self, done: u64) {
+        self.update_at(Instant::now(), done);
+    }
+
+    fn update_at(&mut self, now: Instant, done: u64) {
+        if let Some((then, before)) = self.last {
+            let elapsed = now.duration_since(then).as_secs_f64();
+            if elapsed > 0.0 {
+                let sample = done.saturating_sub(before) as f64 / elapsed;
+                self.rate = Some(match self.rate {
+                    Some(rate) => rate + SMOOTHING * (sample - rate),
+                    None => sample,
+                });
+            }
+        }
+        self.last = Some((now, done));
+    }
+
+    /// Starts measuring from now on, so the time spent paused doesn't count.
+    fn restart(&mut self) {
+        if let Some((_, done)) = self.last {
+            self.last = Some((Instant::now(), done));
+        }
+    }
+
+    fn time_left(&self, done: u64, total: u64) -> Option<Duration> {
+        match self.rate {
+            Some(rate) if rate > 0.0 => Some(Duration::from_secs_f64(
+                total.saturating_sub(done) as f64 / rate,
+            )),
+            _ => None,
+        }
+    }
+}
+
+const NOTIFICATION_ID: &str = "job-finished";
+
+pub struct Widgets {
+    pub window: gtk::ApplicationWindow,
+    pub header: Header,
+    pub view_stack: gtk::Stack,
+    pub main_view: MainView,
+    pub history_view: HistoryView,
+    pub complete_view: CompleteView,
+    pub paused_view: PausedView,
+    pub cancelled_view: CancelledView,
+    pub failed_view: FailedView,
+    /// Goes back to the queue after one of the views above was shown for a moment.
+    back_source: RefCell<Option<glib::SourceId>>,
+    /// How many jobs finished since the user last looked.
+    unseen: Cell<u32>,
+}
+
+impl Widgets {
+    pub fn new(application: &gtk::Application) -> Self {
+        let complete_view = CompleteView::new();
+        let paused_view = PausedView::new();
+        let cancelled_view = CancelledView::new();
+        let failed_view = FailedView::new();
+        let history_view = HistoryView::new();
+        let main_view = MainView::new();
+
+        let view_stack = gtk::Stack::new();
+        view_stack.set_border_width(6);
+        view_stack.set_vexpand(true);
+        view_stack.set_hexpand(true);
+        view_stack.add_titled(&main_view.container, "queue", "Queue");
+        view_stack.add_titled(&history_view.container, "history", "History");
+        view_stack.add_named(&complete_view.container, "complete");
+        view_stack.add_named(&paused_view.container, "paused");
+        view_stack.add_named(&cancelled_view.container, "cancelled");
+        view_stack.add_named(&failed_view.container, "failed");
+
+        let header = Header::new(&view_stack);
+
+        let window = gtk::ApplicationWindow::new(application);
+        window.set_icon_name(Some("package-x-generic"));
+        window.set_title("Progress Tracker");
+        window.set_property_window_position(gtk::WindowPosition::Center);
+        window.set_titlebar(Some(&header.container));
+        window.add(&view_stack);
+        window.show_all();
+        window.set_default_size(500, 250);
+        window.connect_delete_event(move |window, _| {
+            window.close();
+            Inhibit(false)
+        });
+
+        Widgets {
+            window,
+            header,
+            view_stack,
+            main_view,
+            history_view,
+            complete_view,
+            paused_view,
+            cancelled_view,
+            failed_view,
+            back_source: RefCell::new(None),
+            unseen: Cell::new(0),
+        }
+    }
+
+    fn visible_view(&self) -> Option<String> {
+        self.view_stack
+            .get_visible_child_name()
+            .map(|name| name.to_string())
+    }
+
+    /// Shows for a moment that the queue ran dry, unless the user is looking at something else.
+    fn show_complete(self: &Rc<Self>) {
+        if self.visible_view().as_deref() == Some("queue") {
+            self.show_briefly(&self.complete_view.container);
+        }
+    }
+
+    /// Shows for a moment that `name` was cancelled, unless the user is looking at the history.
+    fn show_cancelled(self: &Rc<Self>, name: &str) {
+        if self.visible_view().as_deref() != Some("history") {
+            self.cancelled_view
+                .label
+                .set_text(&format!("{} was cancelled", name));
+            self.show_briefly(&self.cancelled_view.container);
+        }
+    }
+
+    /// Shows for a moment why `name` failed, unless the user is looking at the history.
+    fn show_failed(self: &Rc<Self>, name: &str, err: &str) {
+        if self.visible_view().as_deref() != Some("history") {
+            self.failed_view.label.set_text(&format!("{} failed", name));
+            self.failed_view.details.set_text(err);
+            self.show_briefly(&self.failed_view.container);
+        }
+    }
+
+    /// Shows `view` for as long as set in the header bar, then goes back to the queue.
+    fn show_briefly<W: IsA<gtk::Widget>>(self: &Rc<Self>, view: &W) {
+        let timeout = self.header.complete_timeout.get_value();
+        if timeout <= 0.0 {
+            return;
+        }
+        if let Some(source) = self.back_source.borrow_mut().take() {
+            glib::source_remove(source);
+        }
+        self.view_stack.set_visible_child(view);
+
+        let source = glib::timeout_add_local(
+            (timeout * 1000.0) as u32,
+            clone!(@weak self as widgets => @default-return glib::Continue(false), move || {
+                widgets.back_source.replace(None);
+                widgets.view_stack.set_visible_child_name("queue");
+                glib::Continue(false)
+            }),
+        );
+        self.back_source.replace(Some(source));
+    }
+
+    /// Shows that all `n` running jobs are paused, unless the user is looking at something else.
+    fn show_paused(&self, n: usize) {
+        let text = if n == 1 {
+            String::from("The running job is paused")
+        } else {
+            format!("All {} running jobs are paused", n)
+        };
+        self.paused_view.label.set_text(&text);
+        if self.visible_view().as_deref() == Some("queue") {
+            self.view_stack
+                .set_visible_child(&self.paused_view.container);
+        }
+    }
+
+    fn hide_paused(&self) {
+        if self.visible_view().as_deref() == Some("paused") {
+            self.view_stack.set_visible_child_name("queue");
+        }
+    }
+
+    /// Asks for whatever the kind of job picked in the main view needs to get going, and hands
+    /// the job over to `on_chosen` unless the user changed their mind.
+    fn choose_job<F: Fn(Box<dyn Job>) + Clone + 'static>(self: &Rc<Self>, on_chosen: F) {
+        match self.main_view.job_type.get_active_id().as_deref() {
+            Some("copy") => {
+                let folder = gtk::FileChooserAction::SelectFolder;
+                self.choose_path(
+                    "Directory to Copy",
+                    folder,
+                    clone!(@weak self as widgets => move |source| {
+                        let on_chosen = on_chosen.clone();
+                        widgets.choose_path("Copy Into", folder, move |into| {
+                            on_chosen(Box::new(CopyDirectory::new(source.clone(), &into)));
+                        });
+                    }),
+                );
+            }
+            Some("hash") => {
+                self.choose_path("File to Hash", gtk::FileChooserAction::Open, move |path| {
+                    on_chosen(Box::new(HashFile::new(path)));
+                });
+            }
+            Some("command") => {
+                let command = &self.main_view.command;
+                match RunCommand::new(&command.get_text()) {
+                    Ok(job) => on_chosen(Box::new(job)),
+                    Err(err) => {
+                        command.get_style_context().add_class("error");
+                        command.set_tooltip_text(Some(&err.to_string()));
+                    }
+                }
+            }
+            _ => (),
+        }
+    }
+
+    /// Lets the user pick a file or directory, which is handed over to `on_chosen` unless the
+    /// dialog was cancelled.
+    fn choose_path<F: Fn(PathBuf) + 'static>(
+        &self,
+        title: &str,
+        action: gtk::FileChooserAction,
+        on_chosen: F,
+    ) {
+        let file_chooser = gtk::FileChooserDialog::new(Some(title), Some(&self.window), action);
+        file_chooser.add_buttons(&[
+            ("Select", gtk::ResponseType::Ok),
+            ("Cancel", gtk::ResponseType::Cancel),
+        ]);
+        file_chooser.set_modal(true);
+
+        file_chooser.connect_response(move |file_chooser, response| {
+            let path = file_chooser
+                .get_filename()
+                .filter(|_| response == gtk::ResponseType::Ok);
+            file_chooser.close();
+            if let Some(path) = path {
+                on_chosen(path);
+            }
+        });
+        file_chooser.show_all();
+    }
+
+    /// Lets the user know that a job finished, unless they're looking already.
+    ///
+    /// All jobs share one notification, which gets replaced by the next one to finish.
+    fn notify(&self, what_happened: &str) {
+        if self.window.is_active() {
+            return;
+        }
+        self.window.set_urgency_hint(true);
+
+        let application = match self.window.get_application() {
+            Some(application) => application,
+            None => return,
+        };
+        let unseen = self.unseen.get() + 1;
+        self.unseen.set(unseen);
+
+        let notification = if unseen == 1 {
+            gio::Notification::new("Job finished")
+        } else {
+            gio::Notification::new(&format!("{} jobs finished", unseen))
+        };
+        notification.set_body(Some(what_happened));
+        notification.set_default_action("app.open-history");
+        notification.add_button("Open", "app.open-history");
+        notification.add_button("Dismiss", "app.dismiss");
+        application.send_notification(Some(NOTIFICATION_ID), &notification);
+    }
+
+    fn withdraw_notification(&self) {
+        self.window.set_urgency_hint(false);
+        if self.unseen.replace(0) == 0 {
+            return;
+        }
+        if let Some(application) = self.window.get_application() {
+            application.withdraw_notification(NOTIFICATION_ID);
+        }
+    }
+}
+
+pub struct Header {
+    container: gtk::HeaderBar,
+    /// How many seconds "All jobs done" is shown for, if at all.
+    pub complete_timeout: gtk::SpinButton,
+}
+
+impl Header {
+    pub fn new(view_stack: &gtk::Stack) -> Self {
+        let switcher = gtk::StackSwitcher::new();
+        switcher.set_stack(Some(view_stack));
+
+        let timeout_label = gtk::Label::new(Some("Show \u{201c}All jobs done\u{201d} for"));
+        let complete_timeout = gtk::SpinButton::with_range(0.0, 10.0, 0.5);
+        complete_timeout.set_digits(1);
+        complete_timeout.set_value(1.5);
+        let seconds_label = gtk::Label::new(Some("seconds"));
+
+        let settings = gtk::Box::new(gtk::Orientation::Horizontal, 6);
+        settings.set_border_width(12);
+        settings.add(&timeout_label);
+        settings.add(&complete_timeout);
+        settings.add(&seconds_label);
+        settings.show_all();
+
+        let menu_button = gtk::MenuButton::new();
+        let menu_image =
+            gtk::Image::from_icon_name(Some("open-menu-symbolic"), gtk::IconSize::Button);
+        menu_button.add(&menu_image);
+        let popover = gtk::Popover::new(Some(&menu_button));
+        popover.add(&settings);
+        menu_button.set_popover(Some(&popover));
+
+        let container = gtk::HeaderBar::new();
+        container.set_custom_title(Some(&switcher));
+        container.set_show_close_button(true);
+        container.pack_end(&menu_button);
+
+        Header {
+            container,
+            complete_timeout,
+        }
+    }
+}
+
+pub struct CompleteView {
+    pub container: gtk::Grid,
+}
+
+impl CompleteView {
+    pub fn new() -> Self {
+        let label = gtk::Label::new(None);
+        label.set_markup("All jobs done");
+        label.set_halign(gtk::Align::Center);
+        label.set_valign(gtk::Align::Center);
+        label.set_vexpand(true);
+        label.set_hexpand(true);
+
+        let container = gtk::Grid::new();
+        container.set_vexpand(true);
+        container.set_hexpand(true);
+        container.add(&label);
+
+        CompleteView { container }
+    }
+}
+
+/// A message in the middle of a view of its own, with room for more below it.
+fn status_view(text: &str) -> (gtk::Grid, gtk::Label) {
+    let label = gtk::Label::new(Some(text));
+    label.set_halign(gtk::Align::Center);
+    label.set_valign(gtk::Align::End);
+    label.set_vexpand(true);
+    label.set_hexpand(true);
+
+    let container = gtk::Grid::new();
+    container.set_row_spacing(12);
+    container.set_vexpand(true);
+    container.set_hexpand(true);
+    container.attach(&label, 0, 0, 1, 1);
+
+    (container, label)
+}
+
+/// Shown while every running job is paused.
+pub struct PausedView {
+    pub container: gtk::Grid,
+    label: gtk::Label,
+    pub resume_button: gtk::Button,
+    /// Goes back to the queue without resuming anything.
+    pub queue_button: gtk::Button,
+}
+
+impl PausedView {
+    fn new() -> Self {
+        let (container, label) = status_view("");
+
+        let resume_button = gtk::Button::with_label("resume all");
+        let queue_button = gtk::Button::with_label("show queue");
+        let buttons = gtk::Box::new(gtk::Orientation::Horizontal, 6);
+        buttons.set_halign(gtk::Align::Center);
+        buttons.set_valign(gtk::Align::Start);
+        buttons.set_vexpand(true);
+        buttons.add(&resume_button);
+        buttons.add(&queue_button);
+        container.attach(&buttons, 0, 1, 1, 1);
+
+        PausedView {
+            container,
+            label,
+            resume_button,
+            queue_button,
+        }
+    }
+}
+
+/// Shown for a moment after a job was cancelled.
+pub struct CancelledView {
+    pub container: gtk::Grid,
+    label: gtk::Label,
+}
+
+impl CancelledView {
+    fn new() -> Self {
+        let (container, label) = status_view("");
+        label.set_valign(gtk::Align::Center);
+
+        CancelledView { container, label }
+    }
+}
+
+/// Shown for a moment after a job failed, along with what went wrong.
+pub struct FailedView {
+    pub container: gtk::Grid,
+    label: gtk::Label,
+    details: gtk::Label,
+}
+
+impl FailedView {
+    fn new() -> Self {
+        let (container, label) = status_view("");
+
+        let details = gtk::Label::new(None);
+        details.set_valign(gtk::Align::Start);
+        details.set_vexpand(true);
+        details.set_line_wrap(true);
+        details.set_selectable(true);
+        details.get_style_context().add_class("dim-label");
+        container.attach(&details, 0, 1, 1, 1);
+
+        FailedView {
+            container,
+            label,
+            details,
+        }
+    }
+}
+
+/// A list of jobs, scrolling once there are too many to fit.
+fn job_list(placeholder: &str) -> (gtk::ScrolledWindow, gtk::ListBox) {
+    let placeholder = gtk::Label::new(Some(placeholder));
+    placeholder.set_sensitive(false);
+
+    let list_box = gtk::ListBox::new();
+    list_box.set_selection_mode(gtk::SelectionMode::None);
+    list_box.set_placeholder(Some(&placeholder));
+
+    let scrolled_window =
+        gtk::ScrolledWindow::new(None::<&gtk::Adjustment>, None::<&gtk::Adjustment>);
+    scrolled_window.set_shadow_type(gtk::ShadowType::In);
+    scrolled_window.set_vexpand(true);
+    scrolled_window.set_hexpand(true);
+    scrolled_window.add(&list_box);
+
+    (scrolled_window, list_box)
+}
+
+pub struct MainView {
+    pub container: gtk::Grid,
+    pub list_box: gtk::ListBox,
+    pub job_type: gtk::ComboBoxText,
+    /// The command line to run, only there for that kind of job.
+    pub command: gtk::Entry,
+    pub button: gtk::Button,
+    pub limit: gtk::SpinButton,
+}
+
+impl MainView {
+    pub fn new() -> Self {
+        let (jobs, list_box) = job_list("Nothing queued");
+
+        let job_type = gtk::ComboBoxText::new();
+        job_type.append(Some("copy"), "Copy a directory");
+        job_type.append(Some("hash"), "SHA-256 of a file");
+        job_type.append(Some("command"), "Run a command");
+        job_type.set_active_id(Some("copy"));
+
+        let command = gtk::Entry::new();
+        command.set_placeholder_text(Some("wget --progress=dot URL"));
+        command.set_hexpand(true);
+        command.set_no_show_all(true);
+        job_type.connect_changed(clone!(@weak command => move |job_type| {
+            command.set_visible(job_type.get_active_id().as_deref() == Some("command"));
+        }));
+        command.connect_changed(|command| {
+            command.get_style_context().remove_class("error");
+            command.set_tooltip_text(None);
+        });
+
+        let button = gtk::Button::new();
+        button.set_label("add job");
+
+        let limit_label = gtk::Label::new(Some("Run at once:"));
+        let limit = gtk::SpinButton::with_range(1.0, 8.0, 1.0);
+        limit.set_value(2.0);
+
+        let controls = gtk::Box::new(gtk::Orientation::Horizontal, 6);
+        controls.pack_start(&job_type, false, false, 0);
+        controls.pack_start(&command, true, true, 0);
+        controls.pack_start(&button, false, false, 0);
+        controls.pack_end(&limit, false, false, 0);
+        controls.pack_end(&limit_label, false, false, 0);
+
+        let container = gtk::Grid::new();
+        container.attach(&jobs, 0, 0, 1, 1);
+        container.attach(&controls, 0, 1, 1, 1);
+        container.set_row_spacing(12);
+        container.set_border_width(6);
+        container.set_vexpand(true);
+        container.set_hexpand(true);
+
+        MainView {
+            container,
+            list_box,
+            job_type,
+            command,
+            button,
+            limit,
+        }
+    }
+}
+
+pub struct HistoryView {
+    pub container: gtk::ScrolledWindow,
+    pub list_box: gtk::ListBox,
+}
+
+impl HistoryView {
+    fn new() -> Self {
+        let (container, list_box) = job_list("No finished jobs yet");
+        container.set_border_width(6);
+
+        HistoryView {
+            container,
+            list_box,
+        }
+    }
+}
+
+/// The row of a job, in the queue and later on in the history.
+pub struct JobRow {
+    pub container: gtk::ListBoxRow,
+    pub progress: gtk::ProgressBar,
+    pub status: gtk::Label,
+    /// Pause and cancel, which go away once the job ended.
+    pub buttons: gtk::Box,
+    pub pause_button: gtk::Button,
+    pub cancel_button: gtk::Button,
+}
+
+impl JobRow {
+    pub fn new(name: &str) -> Self {
+        let name = gtk::Label::new(Some(name));
+        name.set_halign(gtk::Align::Start);
+
+        let status = gtk::Label::new(Some("Queued"));
+        status.set_halign(gtk::Align::End);
+        status.set_hexpand(true);
+        status.set_ellipsize(pango::EllipsizeMode::Middle);
+
+        let progress = gtk::ProgressBar::new();
+        progress.set_hexpand(true);
+
+        // Can't be paused before it even started.
+        let pause_button = gtk::Button::new();
+        pause_button.set_label("pause");
+        pause_button.set_sensitive(false);
+        let cancel_button = gtk::Button::new();
+        cancel_button.set_label("cancel");
+
+        let buttons = gtk::Box::new(gtk::Orientation::Horizontal, 6);
+        buttons.set_valign(gtk::Align::Center);
+        buttons.add(&pause_button);
+        buttons.add(&cancel_button);
+
+        let grid = gtk::Grid::new();
+        grid.attach(&name, 0, 0, 1, 1);
+        grid.attach(&status, 1, 0, 1, 1);
+        grid.attach(&progress, 0, 1, 2, 1);
+        grid.attach(&buttons, 2, 0, 1, 2);
+        grid.set_row_spacing(6);
+        grid.set_column_spacing(12);
+        grid.set_border_width(6);
+
+        let container = gtk::ListBoxRow::new();
+        container.set_activatable(false);
+        container.add(&grid);
+
+        JobRow {
+            container,
+            progress,
+            status,
+            buttons,
+            pause_button,
+            cancel_button,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn throughput_needs_two_measurements() {
+        let start = Instant::now();
+        let mut throughput = Throughput::default();
+        throughput.update_at(start, 0);
+        assert_eq!(throughput.rate, None);
+        assert_eq!(throughput.time_left(0, 100), None);
+
+        throughput.update_at(start + Duration::from_secs(2), 100);
+        assert_eq!(throughput.rate, Some(50.0));
+    }
+
+    #[test]
+    fn throughput_is_smoothed() {
+        let start = Instant::now();
+        let mut throughput = Throughput::default();
+        throughput.update_at(start, 0);
+        throughput.update_at(start + Duration::from_secs(1), 100);
+        // A sudden jump only counts for part of it.
+        throughput.update_at(start + Duration::from_secs(2), 300);
+        assert_eq!(throughput.rate, Some(100.0 + SMOOTHING * 100.0));
+    }
+
+    #[test]
+    fn throughput_ignores_measurements_without_time_passing() {
+        let start = Instant::now();
+        let mut throughput = Throughput::default();
+        throughput.update_at(start, 0);
+        throughput.update_at(start + Duration::from_secs(1), 100);
+        throughput.update_at(start + Duration::from_secs(1), 200);
+        assert_eq!(throughput.rate, Some(100.0));
+    }
+
+    #[test]
+    fn time_left_follows_the_rate() {
+        let start = Instant::now();
+        let mut throughput = Throughput::default();
+        throughput.update_at(start, 0);
+        throughput.update_at(start + Duration::from_secs(1), 100);
+        assert_eq!(
+            throughput.time_left(100, 1000),
+            Some(Duration::from_secs(9))
+        );
+        assert_eq!(
+            throughput.time_left(1000, 1000),
+            Some(Duration::from_secs(0))
+        );
+
+        // Nothing is getting done, so there's no telling how long the rest takes.
+        throughput.rate = Some(0.0);
+        assert_eq!(throughput.time_left(100, 1000), None);
+    }
+
+    #[test]
+    fn time_is_formatted() {
+        assert_eq!(format_duration(Duration::from_secs(42)), "42 s");
+        assert_eq!(format_duration(Duration::from_secs(61)), "1 min 1 s");
+        assert_eq!(
+            format_duration(Duration::from_secs(3 * 3600 + 120)),
+            "3 h 2 min"
+        );
+    }
+}
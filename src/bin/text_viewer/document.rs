@@ -0,0 +1,784 @@
+//! A file open in a tab: its buffer and everything needed to load, follow and save it.
+
+use std::cell::{Cell, RefCell};
+use std::error::Error;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+
+use futures::future::{self, AbortHandle};
+use gio::prelude::*;
+use glib::clone;
+use gtk::prelude::*;
+use gtk::Builder;
+use gtk_rs_examples::undo::UndoManager;
+
+use crate::encoding::{encoding_selector, Decoder, Encoding};
+use crate::highlight::{Highlighter, Language};
+use crate::lines::{self, GoToLine};
+use crate::notebook::{Notebook, Tab};
+use crate::search::{Search, SEARCH_DELAY_MS};
+use crate::{APP_TITLE, CHUNK_SIZE};
+
+pub struct Document {
+    window: gtk::ApplicationWindow,
+    notebook: gtk::Notebook,
+    search: Rc<Search>,
+    pub page: gtk::Box,
+    pub tab: Tab,
+    pub text_view: gtk::TextView,
+    pub buffer: gtk::TextBuffer,
+    pub undo: Rc<UndoManager>,
+    pub go_to_line: Rc<GoToLine>,
+    info_bar: gtk::InfoBar,
+    info_label: gtk::Label,
+    change_bar: gtk::InfoBar,
+    change_label: gtk::Label,
+    load_box: gtk::Box,
+    load_progress: gtk::ProgressBar,
+    highlighter: Rc<Highlighter>,
+    path: RefCell<Option<PathBuf>>,
+    encoding: RefCell<Encoding>,
+    loading: RefCell<Option<AbortHandle>>,
+    monitor: RefCell<Option<gio::FileMonitor>>,
+    /// How much of the file made it into the buffer, which is where following picks up.
+    loaded_size: Cell<u64>,
+    appending: RefCell<Option<AbortHandle>>,
+    append_queued: Cell<bool>,
+    follow: Cell<bool>,
+    /// Refreshes the highlighted matches once typing paused.
+    search_source: RefCell<Option<glib::SourceId>>,
+}
+
+impl Document {
+    /// Creates an empty document in a new tab of `notebook`.
+    pub fn new(
+        window: &gtk::ApplicationWindow,
+        notebook: &Notebook,
+        search: Rc<Search>,
+    ) -> Rc<Self> {
+        let glade_src = include_str!("document.glade");
+        let builder = Builder::new();
+        builder
+            .add_from_string(glade_src)
+            .expect("Couldn't add from string");
+
+        let page: gtk::Box = builder
+            .get_object("document")
+            .expect("Couldn't get document");
+        let text_view: gtk::TextView = builder
+            .get_object("text_view")
+            .expect("Couldn't get text_view");
+        let buffer = text_view.get_buffer().expect("Couldn't get buffer");
+        let info_bar: gtk::InfoBar = builder
+            .get_object("info_bar")
+            .expect("Couldn't get info_bar");
+        let info_label: gtk::Label = builder
+            .get_object("info_label")
+            .expect("Couldn't get info_label");
+        let change_bar: gtk::InfoBar = builder
+            .get_object("change_bar")
+            .expect("Couldn't get change_bar");
+        let change_label: gtk::Label = builder
+            .get_object("change_label")
+            .expect("Couldn't get change_label");
+        let load_box: gtk::Box = builder
+            .get_object("load_box")
+            .expect("Couldn't get load_box");
+        let load_progress: gtk::ProgressBar = builder
+            .get_object("load_progress")
+            .expect("Couldn't get load_progress");
+        let cancel_button: gtk::Button = builder
+            .get_object("cancel_button")
+            .expect("Couldn't get cancel_button");
+
+        let tab = notebook.create_tab("Untitled", &page);
+        let highlighter = Highlighter::new(&buffer);
+        let undo = UndoManager::new(&text_view);
+        let go_to_line = GoToLine::new(&text_view);
+        lines::show_line_numbers(&text_view);
+        lines::highlight_current_line(&buffer);
+
+        let document = Rc::new(Document {
+            window: window.clone(),
+            notebook: notebook.notebook.clone(),
+            search,
+            page,
+            tab,
+            text_view,
+            buffer,
+            undo,
+            go_to_line,
+            info_bar,
+            info_label,
+            change_bar,
+            change_label,
+            load_box,
+            load_progress,
+            highlighter,
+            path: RefCell::new(None),
+            encoding: RefCell::new(Encoding::default()),
+            loading: RefCell::new(None),
+            monitor: RefCell::new(None),
+            loaded_size: Cell::new(0),
+            appending: RefCell::new(None),
+            append_queued: Cell::new(false),
+            follow: Cell::new(false),
+            search_source: RefCell::new(None),
+        });
+
+        document
+            .buffer
+            .connect_modified_changed(clone!(@weak document => move |_| {
+                document.update_title();
+            }));
+        document
+            .buffer
+            .connect_changed(clone!(@weak document => move |_| {
+                document.queue_search_update();
+            }));
+        document
+            .info_bar
+            .connect_response(|info_bar, _| info_bar.hide());
+        document.change_bar.connect_response(
+            clone!(@weak document => move |change_bar, response| {
+                change_bar.hide();
+                if response == gtk::ResponseType::Accept {
+                    document.reload();
+                }
+            }),
+        );
+        cancel_button.connect_clicked(clone!(@weak document => move |_| {
+            document.cancel_loading();
+            document.set_loading(false);
+            document.forget_file();
+        }));
+        document.update_title();
+
+        document
+    }
+
+    pub fn path(&self) -> Option<PathBuf> {
+        self.path.borrow().clone()
+    }
+
+    pub fn is_current(&self) -> bool {
+        let current = self.notebook.get_current_page();
+        current.is_some() && self.notebook.page_num(&self.page) == current
+    }
+
+    pub fn is_loading(&self) -> bool {
+        self.loading.borrow().is_some()
+    }
+
+    /// Whether this is still the blank document a window starts out with.
+    pub fn is_blank(&self) -> bool {
+        self.path.borrow().is_none()
+            && !self.is_loading()
+            && !self.buffer.get_modified()
+            && self.buffer.get_char_count() == 0
+    }
+
+    /// Refreshes whatever the window shows about the current document.
+    pub fn present(&self) {
+        self.update_window_title();
+        self.update_actions();
+        if self.search.is_active() {
+            self.search.update(&self.buffer);
+        }
+    }
+
+    /// Refreshes the highlighted matches once nothing was typed for a moment, rather than
+    /// searching the whole buffer again after every key.
+    fn queue_search_update(self: &Rc<Self>) {
+        if let Some(source) = self.search_source.borrow_mut().take() {
+            glib::source_remove(source);
+        }
+        if !self.search.is_active() || self.is_loading() {
+            return;
+        }
+
+        let source = glib::timeout_add_local(
+            SEARCH_DELAY_MS,
+            clone!(@weak self as document => @default-return glib::Continue(false), move || {
+                document.search_source.replace(None);
+                if document.search.is_active() && document.is_current() {
+                    document.search.update(&document.buffer);
+                }
+                glib::Continue(false)
+            }),
+        );
+        self.search_source.replace(Some(source));
+    }
+
+    pub fn display_name(&self) -> String {
+        match *self.path.borrow() {
+            Some(ref path) => path
+                .file_name()
+                .unwrap_or_else(|| path.as_os_str())
+                .to_string_lossy()
+                .into_owned(),
+            None => "Untitled".to_owned(),
+        }
+    }
+
+    /// Prefixes the title with a `*` while the buffer holds unsaved changes.
+    fn update_title(&self) {
+        let marker = if self.buffer.get_modified() { "*" } else { "" };
+        self.tab
+            .label
+            .set_text(&format!("{}{}", marker, self.display_name()));
+        let path = self.path.borrow();
+        let tooltip = path.as_ref().map(|path| path.to_string_lossy());
+        self.tab.header.set_tooltip_text(tooltip.as_deref());
+
+        if self.is_current() {
+            self.update_window_title();
+        }
+    }
+
+    fn update_window_title(&self) {
+        self.window
+            .set_title(&format!("{} - {}", self.tab.label.get_text(), APP_TITLE));
+    }
+
+    pub fn show_error(&self, message: &str) {
+        self.info_label.set_text(message);
+        self.info_bar.show();
+    }
+
+    /// Locks the buffer and the save actions while a file is streamed in.
+    fn set_loading(&self, loading: bool) {
+        self.text_view.set_editable(!loading);
+        self.load_box.set_visible(loading);
+        self.load_progress.set_fraction(0.0);
+        self.load_progress.set_text(None);
+
+        if self.is_current() {
+            self.update_actions();
+        }
+    }
+
+    fn update_actions(&self) {
+        if let Some(application) = self.window.get_application() {
+            for name in &["save", "save-as"] {
+                if let Some(action) = application
+                    .lookup_action(name)
+                    .and_then(|action| action.downcast::<gio::SimpleAction>().ok())
+                {
+                    action.set_enabled(!self.is_loading());
+                }
+            }
+        }
+    }
+
+    /// Stops the running load, if any. Whoever cancels is in charge of tidying up afterwards.
+    pub fn cancel_loading(&self) {
+        if let Some(handle) = self.loading.borrow_mut().take() {
+            handle.abort();
+        }
+        if let Some(handle) = self.appending.borrow_mut().take() {
+            handle.abort();
+        }
+        self.append_queued.set(false);
+    }
+
+    /// Starts watching `path` for changes made by someone else.
+    fn watch(self: &Rc<Self>, path: &Path) {
+        self.unwatch();
+
+        let monitor = match gio::File::new_for_path(path)
+            .monitor_file(gio::FileMonitorFlags::NONE, gio::NONE_CANCELLABLE)
+        {
+            Ok(monitor) => monitor,
+            Err(err) => {
+                return self.show_error(&format!(
+                    "Won't notice changes to {}: {}",
+                    path.display(),
+                    err
+                ))
+            }
+        };
+        monitor.connect_changed(clone!(@weak self as document => move |_, _, _, event| {
+            document.file_changed(event);
+        }));
+        *self.monitor.borrow_mut() = Some(monitor);
+    }
+
+    pub fn unwatch(&self) {
+        if let Some(monitor) = self.monitor.borrow_mut().take() {
+            monitor.cancel();
+        }
+        self.change_bar.hide();
+    }
+
+    fn file_changed(self: &Rc<Self>, event: gio::FileMonitorEvent) {
+        // Whatever changed will be picked up by the load anyway.
+        if self.is_loading() {
+            return;
+        }
+
+        match event {
+            gio::FileMonitorEvent::Changed
+            | gio::FileMonitorEvent::ChangesDoneHint
+            | gio::FileMonitorEvent::Created => {
+                if self.follow.get() && !self.buffer.get_modified() {
+                    self.follow();
+                } else if event != gio::FileMonitorEvent::Changed {
+                    // Plain changes come in bursts while the file is written, the hint once
+                    // it is done.
+                    self.show_change("changed on disk", true);
+                }
+            }
+            gio::FileMonitorEvent::Deleted => self.show_change("was deleted from disk", false),
+            _ => {}
+        }
+    }
+
+    fn show_change(&self, what: &str, can_reload: bool) {
+        let mut message = format!("{} {}.", self.display_name(), what);
+        if can_reload && self.buffer.get_modified() {
+            message.push_str(" Reloading it throws away your changes.");
+        }
+        self.change_label.set_text(&message);
+        self.change_bar
+            .set_response_sensitive(gtk::ResponseType::Accept, can_reload);
+        self.change_bar.show();
+    }
+
+    /// Reads the file again, sticking to the encoding it was read with the first time.
+    fn reload(self: &Rc<Self>) {
+        let path = self.path.borrow().clone();
+        if let Some(path) = path {
+            let charset = self.encoding.borrow().charset;
+            self.load(path, Some(charset));
+        }
+    }
+
+    pub fn is_following(&self) -> bool {
+        self.follow.get()
+    }
+
+    /// Turns follow mode on or off. While it is on, whatever gets appended to the file shows up
+    /// at the end of the buffer.
+    pub fn set_following(self: &Rc<Self>, follow: bool) {
+        if follow == self.follow.replace(follow) || !follow {
+            return;
+        }
+
+        self.change_bar.hide();
+        if !self.buffer.get_modified() {
+            self.follow();
+        }
+        self.scroll_to_end();
+    }
+
+    /// Appends whatever got added to the end of the file since it was read.
+    fn follow(self: &Rc<Self>) {
+        if self.appending.borrow().is_some() {
+            self.append_queued.set(true);
+            return;
+        }
+        let path = match *self.path.borrow() {
+            Some(ref path) => path.clone(),
+            None => return,
+        };
+
+        let (append, handle) = future::abortable(read_appended(
+            gio::File::new_for_path(&path),
+            self.loaded_size.get(),
+            self.encoding.borrow().charset,
+            self.buffer.clone(),
+        ));
+        *self.appending.borrow_mut() = Some(handle);
+
+        let document = Rc::downgrade(self);
+        glib::MainContext::default().spawn_local(async move {
+            let result = match append.await {
+                Ok(result) => result,
+                Err(future::Aborted) => return,
+            };
+            let document = match document.upgrade() {
+                Some(document) => document,
+                None => return,
+            };
+            document.appending.borrow_mut().take();
+
+            match result {
+                Ok(Some(appended)) => {
+                    document
+                        .loaded_size
+                        .set(document.loaded_size.get() + appended);
+                    document.scroll_to_end();
+                    document.queue_search_update();
+                }
+                // The file got shorter, so it was rewritten rather than appended to.
+                Ok(None) => return document.reload(),
+                Err(err) => {
+                    document.show_error(&format!("Couldn't follow {}: {}", path.display(), err))
+                }
+            }
+
+            if document.append_queued.replace(false) {
+                document.follow();
+            }
+        });
+    }
+
+    fn scroll_to_end(&self) {
+        // Scrolling to a mark waits until the new lines have been laid out, unlike an iter.
+        let mark = self
+            .buffer
+            .create_mark(None, &self.buffer.get_end_iter(), false)
+            .expect("Couldn't create mark");
+        self.text_view.scroll_to_mark(&mark, 0.0, false, 0.0, 1.0);
+        self.buffer.delete_mark(&mark);
+    }
+
+    /// Loads `path`, detecting its encoding unless `charset` says which one to use.
+    pub fn load(self: &Rc<Self>, path: PathBuf, charset: Option<&'static str>) {
+        self.cancel_loading();
+        self.unwatch();
+        self.info_bar.hide();
+        self.buffer.set_text("");
+        self.buffer.set_modified(false);
+        *self.path.borrow_mut() = Some(path.clone());
+        self.update_title();
+        // Most files can be told apart by their name already, so they get highlighted while
+        // they stream in. The rest get another look at their contents once they are loaded.
+        self.highlighter.set_language(Language::detect(&path, &[]));
+
+        // Dropping the read future also cancels the pending gio operation.
+        let (read, handle) = future::abortable(read_into_buffer(
+            gio::File::new_for_path(&path),
+            charset,
+            self.buffer.clone(),
+            self.load_progress.clone(),
+        ));
+        *self.loading.borrow_mut() = Some(handle);
+        self.set_loading(true);
+
+        let document = Rc::downgrade(self);
+        glib::MainContext::default().spawn_local(async move {
+            let result = match read.await {
+                Ok(result) => result,
+                Err(future::Aborted) => return,
+            };
+            let document = match document.upgrade() {
+                Some(document) => document,
+                None => return,
+            };
+            document.loading.borrow_mut().take();
+            document.set_loading(false);
+            document.queue_search_update();
+
+            match result {
+                Ok((encoding, size)) => {
+                    *document.encoding.borrow_mut() = encoding;
+                    document.loaded_size.set(size);
+                    document.watch(&path);
+                    if document.follow.get() {
+                        document.scroll_to_end();
+                    }
+                    if document.highlighter.language().is_none() {
+                        document.detect_language();
+                    }
+                }
+                Err(err) => {
+                    document.forget_file();
+                    document.show_error(&format!("Couldn't open {}: {}", path.display(), err));
+                }
+            }
+        });
+    }
+
+    /// Throws away a half-loaded file so it can't be mistaken for the real thing.
+    fn forget_file(&self) {
+        self.buffer.set_text("");
+        self.buffer.set_modified(false);
+        *self.path.borrow_mut() = None;
+        *self.encoding.borrow_mut() = Encoding::default();
+        self.loaded_size.set(0);
+        self.unwatch();
+        self.highlighter.set_language(None);
+        self.update_title();
+    }
+
+    /// Picks the highlighting from the file name and the start of the buffer.
+    fn detect_language(&self) {
+        let path = match *self.path.borrow() {
+            Some(ref path) => path.clone(),
+            None => return,
+        };
+        let start = self.buffer.get_start_iter();
+        let mut end = start.clone();
+        end.forward_chars(4096);
+        let sample = self
+            .buffer
+            .get_slice(&start, &end, true)
+            .expect("Couldn't get text");
+        self.highlighter
+            .set_language(Language::detect(&path, sample.as_bytes()));
+    }
+
+    fn write_to(self: &Rc<Self>, path: &Path, encoding: Encoding) -> Result<(), Box<dyn Error>> {
+        let (start, end) = self.buffer.get_bounds();
+        let contents = self
+            .buffer
+            .get_text(&start, &end, true)
+            .expect("Couldn't get text");
+        let bytes = encoding
+            .encode(&contents)
+            .map_err(|err| format!("can't encode as {}: {}", encoding.charset, err))?;
+        fs::write(path, &bytes)?;
+        self.loaded_size.set(bytes.len() as u64);
+        // Starting over means our own write doesn't show up as a change made by someone else.
+        self.watch(path);
+
+        let renamed = self.path.borrow().as_deref() != Some(path);
+        *self.path.borrow_mut() = Some(path.to_owned());
+        *self.encoding.borrow_mut() = encoding;
+        if renamed {
+            self.detect_language();
+        }
+        self.buffer.set_modified(false);
+        self.update_title();
+        Ok(())
+    }
+
+    /// Writes the buffer back to its file, asking for a file name first if it never had one.
+    /// `on_saved` only runs once the contents actually made it to disk.
+    pub fn save<F: Fn(&Rc<Self>) + Clone + 'static>(self: &Rc<Self>, on_saved: F) {
+        let path = self.path.borrow().clone();
+        let encoding = *self.encoding.borrow();
+        match path {
+            Some(path) => match self.write_to(&path, encoding) {
+                Ok(()) => on_saved(self),
+                Err(err) => self.show_error(&format!("Couldn't save {}: {}", path.display(), err)),
+            },
+            None => self.save_as(on_saved),
+        }
+    }
+
+    pub fn save_as<F: Fn(&Rc<Self>) + Clone + 'static>(self: &Rc<Self>, on_saved: F) {
+        let file_chooser = gtk::FileChooserDialog::new(
+            Some("Save File"),
+            Some(&self.window),
+            gtk::FileChooserAction::Save,
+        );
+        file_chooser.add_buttons(&[
+            ("Save", gtk::ResponseType::Ok),
+            ("Cancel", gtk::ResponseType::Cancel),
+        ]);
+        file_chooser.set_do_overwrite_confirmation(true);
+        match *self.path.borrow() {
+            Some(ref path) => {
+                file_chooser.set_filename(path);
+            }
+            None => file_chooser.set_current_name("Untitled.txt"),
+        }
+        let (selector, encodings) = encoding_selector(false);
+        let current = *self.encoding.borrow();
+        encodings.set_active_id(Some(current.charset));
+        file_chooser.set_extra_widget(&selector);
+
+        file_chooser.connect_response(clone!(@weak self as document => move |file_chooser, response| {
+            let path = match file_chooser.get_filename() {
+                Some(path) if response == gtk::ResponseType::Ok => path,
+                _ => {
+                    file_chooser.close();
+                    return;
+                }
+            };
+            let encoding = encodings
+                .get_active_id()
+                .and_then(|id| Encoding::from_charset(&id))
+                .map_or(current, |charset| Encoding {
+                    charset,
+                    // Only keep the byte order mark if the file stays in the same encoding.
+                    bom: current.bom && charset == current.charset,
+                });
+            file_chooser.close();
+            match document.write_to(&path, encoding) {
+                Ok(()) => on_saved(&document),
+                Err(err) => document.show_error(&format!("Couldn't save {}: {}", path.display(), err)),
+            }
+        }));
+
+        file_chooser.show_all();
+    }
+
+    /// Runs `then` straight away if there is nothing to lose, otherwise only once the user chose
+    /// to either save or throw away the pending changes.
+    pub fn confirm_discard<F: Fn(&Rc<Self>) + Clone + 'static>(self: &Rc<Self>, then: F) {
+        if !self.buffer.get_modified() {
+            then(self);
+            return;
+        }
+
+        let dialog = gtk::MessageDialog::new(
+            Some(&self.window),
+            gtk::DialogFlags::MODAL | gtk::DialogFlags::DESTROY_WITH_PARENT,
+            gtk::MessageType::Question,
+            gtk::ButtonsType::None,
+            &format!("Save changes to \"{}\"?", self.display_name()),
+        );
+        dialog
+            .set_property_secondary_text(Some("Your changes will be lost if you don't save them."));
+        dialog.add_buttons(&[
+            ("Discard", gtk::ResponseType::Reject),
+            ("Cancel", gtk::ResponseType::Cancel),
+            ("Save", gtk::ResponseType::Accept),
+        ]);
+        dialog.set_default_response(gtk::ResponseType::Accept);
+
+        dialog.connect_response(clone!(@weak self as document => move |dialog, response| {
+            dialog.close();
+            match response {
+                gtk::ResponseType::Accept => document.save(then.clone()),
+                gtk::ResponseType::Reject => then(&document),
+                _ => {}
+            }
+        }));
+
+        dialog.show_all();
+    }
+}
+
+fn format_size(size: u64) -> String {
+    glib::format_size(size)
+        .map(|size| size.to_string())
+        .unwrap_or_else(|| format!("{} bytes", size))
+}
+
+/// Streams `file` into `buffer` one chunk at a time, reporting how far it got on `progress`.
+/// Unless `charset` is given, the encoding is detected from the first chunk.
+async fn read_into_buffer(
+    file: gio::File,
+    charset: Option<&'static str>,
+    buffer: gtk::TextBuffer,
+    progress: gtk::ProgressBar,
+) -> Result<(Encoding, u64), glib::Error> {
+    let info = file
+        .query_info_async_future(
+            "standard::size",
+            gio::FileQueryInfoFlags::NONE,
+            glib::PRIORITY_DEFAULT,
+        )
+        .await?;
+    let total = info.get_size().max(0) as u64;
+    let total_text = format_size(total);
+
+    let stream = file.read_async_future(glib::PRIORITY_DEFAULT).await?;
+
+    let mut buf = vec![0; CHUNK_SIZE];
+    let mut pending = Vec::new();
+    let mut done = 0u64;
+    let mut encoding = Encoding::default();
+    let mut decoder = None;
+
+    loop {
+        let (b, len) = stream
+            .read_async_future(buf, glib::PRIORITY_DEFAULT)
+            .await
+            .map_err(|(_buf, err)| err)?;
+        buf = b;
+
+        if len == 0 {
+            break;
+        }
+
+        pending.extend_from_slice(&buf[..len]);
+        if decoder.is_none() {
+            encoding = match charset {
+                Some(charset) => Encoding::forced(charset, &pending),
+                None => Encoding::detect(&pending),
+            };
+            pending.drain(..encoding.byte_order_mark().len().min(pending.len()));
+            decoder = Some(Decoder::new(encoding.charset)?);
+        }
+
+        let decoder = decoder
+            .as_ref()
+            .expect("Decoder is set up with the first chunk");
+        let (text, consumed) = decoder.decode(&pending, false)?;
+        pending.drain(..consumed);
+        buffer.insert(&mut buffer.get_end_iter(), &text);
+        // Loading isn't editing.
+        buffer.set_modified(false);
+
+        done += len as u64;
+        if total > 0 {
+            progress.set_fraction((done as f64 / total as f64).min(1.0));
+        }
+        progress.set_text(Some(&format!("{} of {}", format_size(done), total_text)));
+    }
+
+    // The file ended in the middle of a character.
+    if let Some(decoder) = decoder {
+        if !pending.is_empty() {
+            let (text, _) = decoder.decode(&pending, true)?;
+            buffer.insert(&mut buffer.get_end_iter(), &text);
+            buffer.set_modified(false);
+        }
+    }
+
+    stream.close_async_future(glib::PRIORITY_DEFAULT).await?;
+
+    Ok((encoding, done))
+}
+
+/// Reads what got appended to `file` after its first `offset` bytes onto the end of `buffer`.
+/// Returns how many bytes it added, or `None` if the file shrank instead.
+async fn read_appended(
+    file: gio::File,
+    offset: u64,
+    charset: &'static str,
+    buffer: gtk::TextBuffer,
+) -> Result<Option<u64>, glib::Error> {
+    let info = file
+        .query_info_async_future(
+            "standard::size",
+            gio::FileQueryInfoFlags::NONE,
+            glib::PRIORITY_DEFAULT,
+        )
+        .await?;
+    let size = info.get_size().max(0) as u64;
+    if size < offset {
+        return Ok(None);
+    } else if size == offset {
+        return Ok(Some(0));
+    }
+
+    let stream = file.read_async_future(glib::PRIORITY_DEFAULT).await?;
+    stream.seek(offset as i64, glib::SeekType::Set, gio::NONE_CANCELLABLE)?;
+
+    let decoder = Decoder::new(charset)?;
+    let mut buf = vec![0; CHUNK_SIZE];
+    let mut pending = Vec::new();
+    let mut appended = 0u64;
+
+    loop {
+        let (b, len) = stream
+            .read_async_future(buf, glib::PRIORITY_DEFAULT)
+            .await
+            .map_err(|(_buf, err)| err)?;
+        buf = b;
+
+        if len == 0 {
+            break;
+        }
+
+        // A character that is only half written yet is left for next time.
+        pending.extend_from_slice(&buf[..len]);
+        let (text, consumed) = decoder.decode(&pending, false)?;
+        pending.drain(..consumed);
+        appended += consumed as u64;
+        buffer.insert(&mut buffer.get_end_iter(), &text);
+        buffer.set_modified(false);
+    }
+
+    stream.close_async_future(glib::PRIORITY_DEFAULT).await?;
+
+    Ok(Some(appended))
+}
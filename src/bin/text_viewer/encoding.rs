@@ -0,0 +1,264 @@
+//! Character encodings: detecting them, decoding files into UTF-8 and encoding them back.
+
+use std::str;
+
+use gio::prelude::*;
+use gtk::prelude::*;
+
+use crate::CHUNK_SIZE;
+
+// Charset names as understood by iconv, along with how the file chooser shows them.
+pub const ENCODINGS: &[(&str, &str)] = &[
+    ("UTF-8", "Unicode (UTF-8)"),
+    ("UTF-16LE", "Unicode (UTF-16 Little Endian)"),
+    ("UTF-16BE", "Unicode (UTF-16 Big Endian)"),
+    ("UTF-32LE", "Unicode (UTF-32 Little Endian)"),
+    ("UTF-32BE", "Unicode (UTF-32 Big Endian)"),
+    ("WINDOWS-1252", "Western (Windows-1252)"),
+    ("ISO-8859-1", "Western (ISO-8859-1)"),
+    ("ISO-8859-15", "Western (ISO-8859-15)"),
+    ("WINDOWS-1250", "Central European (Windows-1250)"),
+    ("WINDOWS-1251", "Cyrillic (Windows-1251)"),
+    ("KOI8-R", "Cyrillic (KOI8-R)"),
+    ("SHIFT_JIS", "Japanese (Shift_JIS)"),
+    ("GB18030", "Chinese Simplified (GB18030)"),
+];
+
+const BYTE_ORDER_MARKS: &[(&str, &[u8])] = &[
+    ("UTF-8", b"\xEF\xBB\xBF"),
+    // Has to be tried before UTF-16LE, whose mark it starts with.
+    ("UTF-32LE", b"\xFF\xFE\0\0"),
+    ("UTF-32BE", b"\0\0\xFE\xFF"),
+    ("UTF-16LE", b"\xFF\xFE"),
+    ("UTF-16BE", b"\xFE\xFF"),
+];
+
+// What most legacy files not in UTF-8 turn out to be.
+const FALLBACK_ENCODING: &str = "WINDOWS-1252";
+
+fn byte_order_mark(charset: &str) -> &'static [u8] {
+    BYTE_ORDER_MARKS
+        .iter()
+        .find(|&&(name, _)| name == charset)
+        .map_or(&[], |&(_, bom)| bom)
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Encoding {
+    pub charset: &'static str,
+    pub bom: bool,
+}
+
+impl Default for Encoding {
+    fn default() -> Self {
+        Encoding {
+            charset: "UTF-8",
+            bom: false,
+        }
+    }
+}
+
+impl Encoding {
+    pub fn from_charset(charset: &str) -> Option<&'static str> {
+        ENCODINGS
+            .iter()
+            .map(|&(name, _)| name)
+            .find(|name| *name == charset)
+    }
+
+    pub fn byte_order_mark(&self) -> &'static [u8] {
+        byte_order_mark(self.charset)
+    }
+
+    /// Guesses the encoding from the first bytes of a file: a byte order mark wins, UTF-16 gives
+    /// itself away through the zero bytes of ASCII characters and anything that isn't valid UTF-8
+    /// is assumed to be Windows-1252.
+    pub fn detect(bytes: &[u8]) -> Self {
+        for &(charset, bom) in BYTE_ORDER_MARKS {
+            if bytes.starts_with(bom) {
+                return Encoding { charset, bom: true };
+            }
+        }
+
+        let units = bytes.len() / 2;
+        let zeros_at = |offset| {
+            bytes
+                .iter()
+                .skip(offset)
+                .step_by(2)
+                .filter(|&&b| b == 0)
+                .count()
+        };
+        let (even_zeros, odd_zeros) = (zeros_at(0), zeros_at(1));
+        let charset = if units > 0 && odd_zeros > units / 4 && even_zeros < odd_zeros / 8 {
+            "UTF-16LE"
+        } else if units > 0 && even_zeros > units / 4 && odd_zeros < even_zeros / 8 {
+            "UTF-16BE"
+        } else {
+            match str::from_utf8(bytes) {
+                Ok(_) => "UTF-8",
+                // Only the last character got cut off.
+                Err(ref err) if err.error_len().is_none() => "UTF-8",
+                Err(_) => FALLBACK_ENCODING,
+            }
+        };
+
+        Encoding {
+            charset,
+            bom: false,
+        }
+    }
+
+    /// Uses `charset` no matter what, only skipping its byte order mark if the file has one.
+    pub fn forced(charset: &'static str, bytes: &[u8]) -> Self {
+        let bom = byte_order_mark(charset);
+        Encoding {
+            charset,
+            bom: !bom.is_empty() && bytes.starts_with(bom),
+        }
+    }
+
+    pub fn encode(&self, text: &str) -> Result<Vec<u8>, glib::Error> {
+        let mut bytes = if self.bom {
+            self.byte_order_mark().to_vec()
+        } else {
+            Vec::new()
+        };
+
+        if self.charset == "UTF-8" {
+            bytes.extend_from_slice(text.as_bytes());
+        } else {
+            // No fallback here: characters the charset can't represent must not get lost silently.
+            let converter = gio::CharsetConverter::new(self.charset, "UTF-8")?;
+            let (converted, _) = convert(&converter, text.as_bytes(), true)?;
+            bytes.extend_from_slice(&converted);
+        }
+
+        Ok(bytes)
+    }
+}
+
+/// Builds the "Character encoding" extra widget for the file choosers, optionally offering to
+/// detect the encoding.
+pub fn encoding_selector(with_auto: bool) -> (gtk::Box, gtk::ComboBoxText) {
+    let encodings = gtk::ComboBoxText::new();
+    if with_auto {
+        encodings.append(Some("auto"), "Automatically Detected");
+    }
+    for &(charset, label) in ENCODINGS {
+        encodings.append(Some(charset), label);
+    }
+
+    let selector = gtk::Box::new(gtk::Orientation::Horizontal, 6);
+    selector.pack_start(
+        &gtk::Label::new(Some("Character encoding:")),
+        false,
+        false,
+        0,
+    );
+    selector.pack_start(&encodings, false, false, 0);
+    selector.show_all();
+
+    (selector, encodings)
+}
+
+/// Decodes as much of `bytes` as forms complete UTF-8 and returns it along with the number of
+/// bytes used. Invalid sequences are replaced with U+FFFD, a character cut in half at the end is
+/// left for the next chunk unless this is the last one.
+fn decode_utf8(bytes: &[u8], at_end: bool) -> (String, usize) {
+    let mut text = String::with_capacity(bytes.len());
+    let mut consumed = 0;
+
+    while consumed < bytes.len() {
+        match str::from_utf8(&bytes[consumed..]) {
+            Ok(valid) => {
+                text.push_str(valid);
+                consumed = bytes.len();
+            }
+            Err(err) => {
+                let valid_up_to = consumed + err.valid_up_to();
+                text.push_str(&String::from_utf8_lossy(&bytes[consumed..valid_up_to]));
+                match err.error_len() {
+                    Some(len) => {
+                        text.push('\u{FFFD}');
+                        consumed = valid_up_to + len;
+                    }
+                    None if at_end => {
+                        text.push('\u{FFFD}');
+                        consumed = bytes.len();
+                    }
+                    None => {
+                        consumed = valid_up_to;
+                        break;
+                    }
+                }
+            }
+        }
+    }
+
+    (text, consumed)
+}
+
+/// Runs `input` through `converter` and returns the result along with the number of bytes used.
+/// A character cut in half at the end of `input` is left over rather than treated as an error.
+fn convert(
+    converter: &gio::CharsetConverter,
+    input: &[u8],
+    at_end: bool,
+) -> Result<(Vec<u8>, usize), glib::Error> {
+    let flags = if at_end {
+        gio::ConverterFlags::INPUT_AT_END
+    } else {
+        gio::ConverterFlags::NONE
+    };
+    let mut output = Vec::with_capacity(input.len());
+    let mut chunk = vec![0; CHUNK_SIZE];
+    let mut used = 0;
+
+    while used < input.len() {
+        match converter.convert(&input[used..], &mut chunk[..], flags) {
+            Ok((_, read, written)) => {
+                used += read;
+                output.extend_from_slice(&chunk[..written]);
+            }
+            Err(ref err) if err.kind() == Some(gio::IOErrorEnum::PartialInput) => break,
+            Err(err) => return Err(err),
+        }
+    }
+
+    Ok((output, used))
+}
+
+/// Turns the raw bytes of a file into the UTF-8 text a `TextBuffer` wants.
+pub enum Decoder {
+    Utf8,
+    Charset(gio::CharsetConverter),
+}
+
+impl Decoder {
+    pub fn new(charset: &str) -> Result<Self, glib::Error> {
+        if charset == "UTF-8" {
+            return Ok(Decoder::Utf8);
+        }
+
+        let converter = gio::CharsetConverter::new("UTF-8", charset)?;
+        // Show undecodable bytes as `\xNN` instead of refusing to open the whole file.
+        converter.set_use_fallback(true);
+        Ok(Decoder::Charset(converter))
+    }
+
+    pub fn decode(&self, bytes: &[u8], at_end: bool) -> Result<(String, usize), glib::Error> {
+        match *self {
+            Decoder::Utf8 => Ok(decode_utf8(bytes, at_end)),
+            Decoder::Charset(ref converter) => {
+                let (converted, mut used) = convert(converter, bytes, at_end)?;
+                let mut text = String::from_utf8_lossy(&converted).into_owned();
+                if at_end && used < bytes.len() {
+                    text.push('\u{FFFD}');
+                    used = bytes.len();
+                }
+                Ok((text, used))
+            }
+        }
+    }
+}
@@ -0,0 +1,1071 @@
+//! A small syntax highlighter for Rust, TOML, JSON and Markdown built on `TextTag`s.
+//!
+//! Lines are tokenized one at a time, starting from the state the previous line left off in
+//! (inside a block comment, a multi-line string, ...). That state is remembered for every line,
+//! so after an edit only the touched lines are looked at again, plus the ones after them for as
+//! long as their starting state keeps changing. The work is done from an idle handler in small
+//! batches to keep the UI responsive even for huge files.
+
+use std::cell::{Cell, RefCell};
+use std::path::Path;
+use std::rc::Rc;
+
+use glib::clone;
+use glib::translate::ToGlib;
+use gtk::prelude::*;
+
+// How many lines to highlight before giving the main loop a chance to do something else.
+const LINES_PER_BATCH: i32 = 500;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Language {
+    Rust,
+    Toml,
+    Json,
+    Markdown,
+}
+
+impl Language {
+    /// Picks the language from the file extension, falling back to the content type GIO guesses
+    /// from the name and the first bytes of the file.
+    pub fn detect(path: &Path, data: &[u8]) -> Option<Self> {
+        let extension = path.extension().and_then(|extension| extension.to_str());
+        match extension {
+            Some("rs") => return Some(Language::Rust),
+            Some("toml") => return Some(Language::Toml),
+            Some("json") => return Some(Language::Json),
+            Some("md") | Some("markdown") => return Some(Language::Markdown),
+            _ => {}
+        }
+
+        let (content_type, _) = gio::content_type_guess(path.to_str(), data);
+        match content_type.as_str() {
+            "text/rust" | "text/x-rust" => Some(Language::Rust),
+            "application/toml" | "text/x-toml" => Some(Language::Toml),
+            "application/json" => Some(Language::Json),
+            "text/markdown" | "text/x-markdown" => Some(Language::Markdown),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Kind {
+    Keyword,
+    Type,
+    String,
+    Number,
+    Comment,
+    Attribute,
+    Key,
+    Heading,
+    Emphasis,
+    Code,
+    Link,
+}
+
+const KINDS: &[Kind] = &[
+    Kind::Keyword,
+    Kind::Type,
+    Kind::String,
+    Kind::Number,
+    Kind::Comment,
+    Kind::Attribute,
+    Kind::Key,
+    Kind::Heading,
+    Kind::Emphasis,
+    Kind::Code,
+    Kind::Link,
+];
+
+impl Kind {
+    fn tag_name(self) -> &'static str {
+        match self {
+            Kind::Keyword => "syntax-keyword",
+            Kind::Type => "syntax-type",
+            Kind::String => "syntax-string",
+            Kind::Number => "syntax-number",
+            Kind::Comment => "syntax-comment",
+            Kind::Attribute => "syntax-attribute",
+            Kind::Key => "syntax-key",
+            Kind::Heading => "syntax-heading",
+            Kind::Emphasis => "syntax-emphasis",
+            Kind::Code => "syntax-code",
+            Kind::Link => "syntax-link",
+        }
+    }
+
+    fn create_tag(self) -> gtk::TextTag {
+        let tag = gtk::TextTag::new(Some(self.tag_name()));
+        match self {
+            Kind::Keyword => {
+                tag.set_property_foreground(Some("#a626a4"));
+                tag.set_property_weight(pango::Weight::Bold.to_glib());
+            }
+            Kind::Type => tag.set_property_foreground(Some("#c18401")),
+            Kind::String => tag.set_property_foreground(Some("#50a14f")),
+            Kind::Number => tag.set_property_foreground(Some("#986801")),
+            Kind::Comment => {
+                tag.set_property_foreground(Some("#a0a1a7"));
+                tag.set_property_style(pango::Style::Italic);
+            }
+            Kind::Attribute => tag.set_property_foreground(Some("#4078f2")),
+            Kind::Key => tag.set_property_foreground(Some("#e45649")),
+            Kind::Heading => {
+                tag.set_property_foreground(Some("#e45649"));
+                tag.set_property_weight(pango::Weight::Bold.to_glib());
+            }
+            Kind::Emphasis => tag.set_property_style(pango::Style::Italic),
+            Kind::Code => {
+                tag.set_property_foreground(Some("#50a14f"));
+                tag.set_property_family(Some("monospace"));
+            }
+            Kind::Link => {
+                tag.set_property_foreground(Some("#4078f2"));
+                tag.set_property_underline(pango::Underline::Single);
+            }
+        }
+        tag
+    }
+}
+
+/// Where a line starts off: everything that can span several lines needs its own state.
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum State {
+    Normal,
+    /// Inside a Rust block comment, these nest.
+    BlockComment(u8),
+    /// Inside a Rust string, which may span lines.
+    Str,
+    /// Inside a Rust raw string with that many `#`s.
+    RawStr(u8),
+    /// Inside a TOML `"""` string.
+    MultiLineBasic,
+    /// Inside a TOML `'''` string.
+    MultiLineLiteral,
+    /// Inside a TOML array that spans lines, so there are no keys to expect.
+    Array(u8),
+    /// Inside a fenced Markdown code block.
+    Fence,
+}
+
+/// A highlighted part of a line, in characters.
+struct Span {
+    start: usize,
+    end: usize,
+    kind: Kind,
+}
+
+/// Collects the spans found on a line.
+struct Line<'a> {
+    chars: &'a [char],
+    spans: Vec<Span>,
+}
+
+impl<'a> Line<'a> {
+    fn at(&self, index: usize) -> Option<char> {
+        self.chars.get(index).copied()
+    }
+
+    fn starts_with(&self, index: usize, pattern: &str) -> bool {
+        pattern
+            .chars()
+            .enumerate()
+            .all(|(offset, c)| self.at(index + offset) == Some(c))
+    }
+
+    fn push(&mut self, start: usize, end: usize, kind: Kind) {
+        if start < end {
+            self.spans.push(Span { start, end, kind });
+        }
+    }
+
+    fn skip_while<F: Fn(char) -> bool>(&self, mut index: usize, f: F) -> usize {
+        while let Some(c) = self.at(index) {
+            if !f(c) {
+                break;
+            }
+            index += 1;
+        }
+        index
+    }
+
+    /// Finds the end of a string whose body starts at `index`, past the closing `quote`.
+    fn string_end(&self, mut index: usize, quote: &str, escapes: bool) -> Option<usize> {
+        while index < self.chars.len() {
+            if escapes && self.chars[index] == '\\' {
+                index += 2;
+            } else if self.starts_with(index, quote) {
+                return Some(index + quote.chars().count());
+            } else {
+                index += 1;
+            }
+        }
+        None
+    }
+}
+
+fn is_ident_start(c: char) -> bool {
+    c.is_alphabetic() || c == '_'
+}
+
+fn is_ident(c: char) -> bool {
+    c.is_alphanumeric() || c == '_'
+}
+
+fn number_end(line: &Line, index: usize) -> usize {
+    let mut end = index;
+    while let Some(c) = line.at(end) {
+        // A single `.` belongs to the number, `..` is a range.
+        let part_of_number =
+            is_ident(c) || (c == '.' && matches!(line.at(end + 1), Some('0'..='9')));
+        if !part_of_number {
+            break;
+        }
+        end += 1;
+    }
+    end
+}
+
+const RUST_KEYWORDS: &[&str] = &[
+    "as", "async", "await", "break", "const", "continue", "crate", "dyn", "else", "enum", "extern",
+    "false", "fn", "for", "if", "impl", "in", "let", "loop", "match", "mod", "move", "mut", "pub",
+    "ref", "return", "self", "Self", "static", "struct", "super", "trait", "true", "type",
+    "unsafe", "use", "where", "while",
+];
+
+const RUST_PRIMITIVES: &[&str] = &[
+    "bool", "char", "f32", "f64", "i8", "i16", "i32", "i64", "i128", "isize", "str", "u8", "u16",
+    "u32", "u64", "u128", "usize",
+];
+
+fn tokenize_rust(line: &mut Line, mut state: State) -> State {
+    let len = line.chars.len();
+    let mut i = 0;
+
+    while i < len {
+        match state {
+            State::BlockComment(depth) => {
+                let start = i;
+                let mut depth = depth;
+                while i < len && depth > 0 {
+                    if line.starts_with(i, "*/") {
+                        depth -= 1;
+                        i += 2;
+                    } else if line.starts_with(i, "/*") {
+                        depth = depth.saturating_add(1);
+                        i += 2;
+                    } else {
+                        i += 1;
+                    }
+                }
+                line.push(start, i, Kind::Comment);
+                state = if depth == 0 {
+                    State::Normal
+                } else {
+                    State::BlockComment(depth)
+                };
+                continue;
+            }
+            State::Str => {
+                let end = line.string_end(i, "\"", true);
+                line.push(i, end.unwrap_or(len), Kind::String);
+                i = end.unwrap_or(len);
+                state = if end.is_some() {
+                    State::Normal
+                } else {
+                    State::Str
+                };
+                continue;
+            }
+            State::RawStr(hashes) => {
+                let closing: String = "\"".chars().chain((0..hashes).map(|_| '#')).collect();
+                let end = line.string_end(i, &closing, false);
+                line.push(i, end.unwrap_or(len), Kind::String);
+                i = end.unwrap_or(len);
+                state = if end.is_some() {
+                    State::Normal
+                } else {
+                    State::RawStr(hashes)
+                };
+                continue;
+            }
+            _ => {}
+        }
+
+        let c = line.chars[i];
+        if line.starts_with(i, "//") {
+            line.push(i, len, Kind::Comment);
+            break;
+        } else if line.starts_with(i, "/*") {
+            state = State::BlockComment(1);
+            line.push(i, i + 2, Kind::Comment);
+            i += 2;
+        } else if c == '"' {
+            line.push(i, i + 1, Kind::String);
+            state = State::Str;
+            i += 1;
+        } else if c == '\'' {
+            // Either a character literal or a lifetime, which is left alone.
+            let end = if line.at(i + 1) == Some('\\') {
+                line.string_end(i + 1, "'", true)
+            } else if line.at(i + 2) == Some('\'') {
+                Some(i + 3)
+            } else {
+                None
+            };
+            match end {
+                Some(end) => {
+                    line.push(i, end, Kind::String);
+                    i = end;
+                }
+                None => i = line.skip_while(i + 1, is_ident),
+            }
+        } else if c == '#' && (line.at(i + 1) == Some('[') || line.starts_with(i + 1, "![")) {
+            let open = if line.at(i + 1) == Some('[') {
+                i + 1
+            } else {
+                i + 2
+            };
+            let mut depth = 0;
+            let mut end = open;
+            while end < len {
+                match line.chars[end] {
+                    '[' => depth += 1,
+                    ']' => depth -= 1,
+                    _ => {}
+                }
+                end += 1;
+                if depth == 0 {
+                    break;
+                }
+            }
+            line.push(i, end, Kind::Attribute);
+            i = end;
+        } else if c.is_ascii_digit() {
+            let end = number_end(line, i);
+            line.push(i, end, Kind::Number);
+            i = end;
+        } else if is_ident_start(c) {
+            let end = line.skip_while(i, is_ident);
+            let word: String = line.chars[i..end].iter().collect();
+
+            // Byte strings and raw strings start out looking like identifiers.
+            if (word == "r" || word == "br") && matches!(line.at(end), Some('#') | Some('"')) {
+                let quote = line.skip_while(end, |c| c == '#');
+                if line.at(quote) == Some('"') {
+                    line.push(i, quote + 1, Kind::String);
+                    state = State::RawStr((quote - end) as u8);
+                    i = quote + 1;
+                    continue;
+                }
+            }
+            if word == "b" && line.at(end) == Some('"') {
+                line.push(i, end + 1, Kind::String);
+                state = State::Str;
+                i = end + 1;
+                continue;
+            }
+
+            if line.at(end) == Some('!') && line.at(end + 1) != Some('=') {
+                line.push(i, end + 1, Kind::Attribute);
+                i = end + 1;
+                continue;
+            }
+            if RUST_KEYWORDS.contains(&word.as_str()) {
+                line.push(i, end, Kind::Keyword);
+            } else if RUST_PRIMITIVES.contains(&word.as_str())
+                || c.is_uppercase() && word.chars().any(|c| c.is_lowercase())
+            {
+                line.push(i, end, Kind::Type);
+            }
+            i = end;
+        } else {
+            i += 1;
+        }
+    }
+
+    state
+}
+
+fn tokenize_toml(line: &mut Line, mut state: State) -> State {
+    let len = line.chars.len();
+    let mut i = line.skip_while(0, char::is_whitespace);
+    // Keys are only found at the start of a line or inside inline tables.
+    let mut expect_key = state == State::Normal;
+    let mut inline_tables = 0;
+
+    if state == State::Normal && line.at(i) == Some('[') {
+        let end = line.string_end(i + 1, "]", false).map_or(len, |end| {
+            if line.at(end) == Some(']') {
+                end + 1
+            } else {
+                end
+            }
+        });
+        line.push(i, end, Kind::Attribute);
+        i = end;
+        expect_key = false;
+    }
+
+    while i < len {
+        match state {
+            State::MultiLineBasic | State::MultiLineLiteral => {
+                let (quote, escapes) = if state == State::MultiLineBasic {
+                    ("\"\"\"", true)
+                } else {
+                    ("'''", false)
+                };
+                let end = line.string_end(i, quote, escapes);
+                line.push(i, end.unwrap_or(len), Kind::String);
+                i = end.unwrap_or(len);
+                if end.is_some() {
+                    state = State::Normal;
+                }
+                continue;
+            }
+            _ => {}
+        }
+
+        let c = line.chars[i];
+        if c == '#' {
+            line.push(i, len, Kind::Comment);
+            break;
+        } else if line.starts_with(i, "\"\"\"") || line.starts_with(i, "'''") {
+            line.push(i, i + 3, Kind::String);
+            state = if c == '"' {
+                State::MultiLineBasic
+            } else {
+                State::MultiLineLiteral
+            };
+            i += 3;
+        } else if c == '"' || c == '\'' {
+            let end = line
+                .string_end(i + 1, if c == '"' { "\"" } else { "'" }, c == '"')
+                .unwrap_or(len);
+            line.push(i, end, if expect_key { Kind::Key } else { Kind::String });
+            i = end;
+        } else if expect_key && (is_ident(c) || c == '-') {
+            let end = line.skip_while(i, |c| is_ident(c) || c == '-' || c == '.');
+            line.push(i, end, Kind::Key);
+            i = end;
+        } else if c == '=' {
+            expect_key = false;
+            i += 1;
+        } else if c == '{' {
+            inline_tables += 1;
+            expect_key = true;
+            i += 1;
+        } else if c == '}' {
+            inline_tables -= 1;
+            i += 1;
+        } else if c == ',' {
+            expect_key = inline_tables > 0 && !matches!(state, State::Array(_));
+            i += 1;
+        } else if c == '[' {
+            state = match state {
+                State::Array(depth) => State::Array(depth.saturating_add(1)),
+                _ => State::Array(1),
+            };
+            i += 1;
+        } else if c == ']' {
+            state = match state {
+                State::Array(depth) if depth > 1 => State::Array(depth - 1),
+                _ => State::Normal,
+            };
+            i += 1;
+        } else if c.is_ascii_digit() || c == '+' || c == '-' {
+            // Covers dates and times too.
+            let end = line.skip_while(i + 1, |c| is_ident(c) || "-:.+".contains(c));
+            line.push(i, end, Kind::Number);
+            i = end;
+        } else if is_ident_start(c) {
+            let end = line.skip_while(i, is_ident);
+            let word: String = line.chars[i..end].iter().collect();
+            match word.as_str() {
+                "true" | "false" => line.push(i, end, Kind::Keyword),
+                "inf" | "nan" => line.push(i, end, Kind::Number),
+                _ => {}
+            }
+            i = end;
+        } else {
+            i += 1;
+        }
+    }
+
+    state
+}
+
+fn tokenize_json(line: &mut Line) -> State {
+    let len = line.chars.len();
+    let mut i = 0;
+
+    while i < len {
+        let c = line.chars[i];
+        if c == '"' {
+            let end = line.string_end(i + 1, "\"", true).unwrap_or(len);
+            let after = line.skip_while(end, char::is_whitespace);
+            let kind = if line.at(after) == Some(':') {
+                Kind::Key
+            } else {
+                Kind::String
+            };
+            line.push(i, end, kind);
+            i = end;
+        } else if c.is_ascii_digit() || c == '-' {
+            let end = line.skip_while(i + 1, |c| c.is_ascii_alphanumeric() || "+-.".contains(c));
+            line.push(i, end, Kind::Number);
+            i = end;
+        } else if c.is_alphabetic() {
+            let end = line.skip_while(i, char::is_alphabetic);
+            let word: String = line.chars[i..end].iter().collect();
+            if word == "true" || word == "false" || word == "null" {
+                line.push(i, end, Kind::Keyword);
+            }
+            i = end;
+        } else {
+            i += 1;
+        }
+    }
+
+    State::Normal
+}
+
+fn tokenize_markdown(line: &mut Line, state: State) -> State {
+    let len = line.chars.len();
+    let indent = line.skip_while(0, |c| c == ' ');
+    let is_fence = line.starts_with(indent, "```") || line.starts_with(indent, "~~~");
+
+    if state == State::Fence {
+        line.push(0, len, Kind::Code);
+        return if is_fence {
+            State::Normal
+        } else {
+            State::Fence
+        };
+    }
+    if is_fence {
+        line.push(0, len, Kind::Code);
+        return State::Fence;
+    }
+
+    let hashes = line.skip_while(indent, |c| c == '#') - indent;
+    let heading_ends = matches!(line.at(indent + hashes), None | Some(' ') | Some('\t'));
+    if hashes > 0 && hashes <= 6 && heading_ends {
+        line.push(0, len, Kind::Heading);
+        return State::Normal;
+    }
+    if line.at(indent) == Some('>') {
+        line.push(0, len, Kind::Comment);
+        return State::Normal;
+    }
+
+    let mut i = indent;
+    let marker_end = line.skip_while(indent, |c| c.is_ascii_digit());
+    if matches!(line.at(indent), Some('-') | Some('*') | Some('+'))
+        && line.at(indent + 1) == Some(' ')
+    {
+        line.push(indent, indent + 1, Kind::Keyword);
+        i = indent + 1;
+    } else if marker_end > indent
+        && matches!(line.at(marker_end), Some('.') | Some(')'))
+        && line.at(marker_end + 1) == Some(' ')
+    {
+        line.push(indent, marker_end + 1, Kind::Keyword);
+        i = marker_end + 1;
+    }
+
+    while i < len {
+        let c = line.chars[i];
+        if c == '`' {
+            let ticks = line.skip_while(i, |c| c == '`') - i;
+            let closing: String = (0..ticks).map(|_| '`').collect();
+            match line.string_end(i + ticks, &closing, false) {
+                Some(end) => {
+                    line.push(i, end, Kind::Code);
+                    i = end;
+                }
+                None => i += ticks,
+            }
+        } else if c == '*' || c == '_' {
+            let run = line.skip_while(i, |d| d == c) - i;
+            let closing: String = (0..run).map(|_| c).collect();
+            match line.string_end(i + run, &closing, false) {
+                Some(end) if end > i + 2 * run => {
+                    line.push(i, end, Kind::Emphasis);
+                    i = end;
+                }
+                _ => i += run,
+            }
+        } else if c == '[' {
+            let link_end = line
+                .string_end(i + 1, "](", false)
+                .and_then(|target| line.string_end(target, ")", false));
+            match link_end {
+                Some(end) => {
+                    line.push(i, end, Kind::Link);
+                    i = end;
+                }
+                None => i += 1,
+            }
+        } else if c == '<'
+            && (line.starts_with(i + 1, "http") || line.starts_with(i + 1, "mailto:"))
+        {
+            let end = line.string_end(i + 1, ">", false).unwrap_or(len);
+            line.push(i, end, Kind::Link);
+            i = end;
+        } else {
+            i += 1;
+        }
+    }
+
+    State::Normal
+}
+
+fn tokenize(language: Language, state: State, chars: &[char]) -> (Vec<Span>, State) {
+    let mut line = Line {
+        chars,
+        spans: Vec::new(),
+    };
+    let state = match language {
+        Language::Rust => tokenize_rust(&mut line, state),
+        Language::Toml => tokenize_toml(&mut line, state),
+        Language::Json => tokenize_json(&mut line),
+        Language::Markdown => tokenize_markdown(&mut line, state),
+    };
+    (line.spans, state)
+}
+
+/// Counts the line breaks in `text` the way `TextBuffer` does.
+fn count_lines(text: &str) -> usize {
+    let mut lines = 0;
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '\r' => {
+                if chars.peek() == Some(&'\n') {
+                    chars.next();
+                }
+                lines += 1;
+            }
+            '\n' | '\u{2029}' => lines += 1,
+            _ => {}
+        }
+    }
+    lines
+}
+
+pub struct Highlighter {
+    buffer: gtk::TextBuffer,
+    language: Cell<Option<Language>>,
+    /// The state every line starts in, one entry per line of the buffer.
+    states: RefCell<Vec<State>>,
+    /// The first and last line that need highlighting again. Lines after that only do if the
+    /// state they start in changed.
+    dirty: Cell<Option<(usize, usize)>>,
+    queued: Cell<bool>,
+}
+
+impl Highlighter {
+    pub fn new(buffer: &gtk::TextBuffer) -> Rc<Self> {
+        let tag_table = buffer.get_tag_table().expect("Couldn't get tag table");
+        for kind in KINDS {
+            tag_table.add(&kind.create_tag());
+        }
+
+        let highlighter = Rc::new(Highlighter {
+            buffer: buffer.clone(),
+            language: Cell::new(None),
+            states: RefCell::new(Vec::new()),
+            dirty: Cell::new(None),
+            queued: Cell::new(false),
+        });
+
+        // Both run before the buffer changes, while the iters still say where the edit goes.
+        buffer.connect_insert_text(clone!(@weak highlighter => move |_, location, text| {
+            highlighter.lines_inserted(location.get_line() as usize, count_lines(text));
+        }));
+        buffer.connect_delete_range(clone!(@weak highlighter => move |_, start, end| {
+            highlighter.lines_deleted(start.get_line() as usize, end.get_line() as usize);
+        }));
+
+        highlighter
+    }
+
+    pub fn language(&self) -> Option<Language> {
+        self.language.get()
+    }
+
+    pub fn set_language(self: &Rc<Self>, language: Option<Language>) {
+        if language == self.language.get() {
+            return;
+        }
+        self.language.set(language);
+
+        let (start, end) = self.buffer.get_bounds();
+        for kind in KINDS {
+            self.buffer
+                .remove_tag_by_name(kind.tag_name(), &start, &end);
+        }
+
+        let lines = self.buffer.get_line_count() as usize;
+        *self.states.borrow_mut() = vec![State::Normal; lines];
+        self.dirty.set(None);
+        if language.is_some() {
+            self.mark_dirty(0, lines - 1);
+        }
+    }
+
+    fn lines_inserted(self: &Rc<Self>, line: usize, count: usize) {
+        if self.language.get().is_none() {
+            return;
+        }
+
+        {
+            let mut states = self.states.borrow_mut();
+            let at = (line + 1).min(states.len());
+            states.splice(at..at, (0..count).map(|_| State::Normal));
+        }
+        let dirty = self.dirty.get().map(|(first, last)| {
+            let shift = |l: usize| if l > line { l + count } else { l };
+            (shift(first), shift(last))
+        });
+        self.dirty.set(dirty);
+        self.mark_dirty(line, line + count);
+    }
+
+    fn lines_deleted(self: &Rc<Self>, first: usize, last: usize) {
+        if self.language.get().is_none() {
+            return;
+        }
+
+        {
+            let mut states = self.states.borrow_mut();
+            let end = (last + 1).min(states.len());
+            let start = (first + 1).min(end);
+            states.drain(start..end);
+        }
+        let dirty = self.dirty.get().map(|(from, to)| {
+            let shift = |l: usize| {
+                if l > last {
+                    l - (last - first)
+                } else if l > first {
+                    first
+                } else {
+                    l
+                }
+            };
+            (shift(from), shift(to))
+        });
+        self.dirty.set(dirty);
+        self.mark_dirty(first, first);
+    }
+
+    fn mark_dirty(self: &Rc<Self>, first: usize, last: usize) {
+        let dirty = match self.dirty.get() {
+            Some((from, to)) => (from.min(first), to.max(last)),
+            None => (first, last),
+        };
+        self.dirty.set(Some(dirty));
+
+        if !self.queued.get() {
+            self.queued.set(true);
+            glib::idle_add_local(
+                clone!(@weak self as highlighter => @default-return glib::Continue(false), move || {
+                    let more = highlighter.highlight_batch();
+                    highlighter.queued.set(more);
+                    glib::Continue(more)
+                }),
+            );
+        }
+    }
+
+    /// Highlights the next few dirty lines and tells whether there is more to do.
+    fn highlight_batch(&self) -> bool {
+        let (language, (mut line, last)) = match (self.language.get(), self.dirty.get()) {
+            (Some(language), Some(dirty)) => (language, dirty),
+            _ => return false,
+        };
+
+        let lines = self.buffer.get_line_count() as usize;
+        let mut states = self.states.borrow_mut();
+        if states.len() != lines {
+            // Some edit didn't add up, so start over from where it happened.
+            states.resize(lines, State::Normal);
+            self.dirty.set(Some((line, lines - 1)));
+            return true;
+        }
+
+        for _ in 0..LINES_PER_BATCH {
+            if line >= lines {
+                self.dirty.set(None);
+                return false;
+            }
+
+            let next = self.highlight_line(language, line, states[line]);
+            line += 1;
+            if line < lines {
+                let changed = states[line] != next;
+                states[line] = next;
+                if line > last && !changed {
+                    self.dirty.set(None);
+                    return false;
+                }
+            }
+        }
+
+        self.dirty.set(Some((line, last.max(line))));
+        true
+    }
+
+    fn highlight_line(&self, language: Language, line: usize, state: State) -> State {
+        let start = self.buffer.get_iter_at_line(line as i32);
+        let mut end = start.clone();
+        if !end.ends_line() {
+            end.forward_to_line_end();
+        }
+
+        for kind in KINDS {
+            self.buffer
+                .remove_tag_by_name(kind.tag_name(), &start, &end);
+        }
+
+        let text = self
+            .buffer
+            .get_slice(&start, &end, true)
+            .expect("Couldn't get line");
+        let chars: Vec<char> = text.chars().collect();
+        let (spans, next) = tokenize(language, state, &chars);
+
+        for span in spans {
+            self.buffer.apply_tag_by_name(
+                span.kind.tag_name(),
+                &self
+                    .buffer
+                    .get_iter_at_line_offset(line as i32, span.start as i32),
+                &self
+                    .buffer
+                    .get_iter_at_line_offset(line as i32, span.end as i32),
+            );
+        }
+
+        next
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Tokenizes `text` line by line the way the buffer is, giving the highlighted parts of
+    /// every line. Parts of the same kind that touch are joined, as they look the same.
+    fn highlight(language: Language, text: &str) -> Vec<Vec<(String, Kind)>> {
+        let mut state = State::Normal;
+        text.lines()
+            .map(|line| {
+                let chars: Vec<char> = line.chars().collect();
+                let (spans, next) = tokenize(language, state, &chars);
+                state = next;
+
+                let mut parts: Vec<(usize, usize, Kind)> = Vec::new();
+                for span in spans {
+                    match parts.last_mut() {
+                        Some(last) if last.1 == span.start && last.2 == span.kind => {
+                            last.1 = span.end
+                        }
+                        _ => parts.push((span.start, span.end, span.kind)),
+                    }
+                }
+                parts
+                    .into_iter()
+                    .map(|(start, end, kind)| (chars[start..end].iter().collect(), kind))
+                    .collect()
+            })
+            .collect()
+    }
+
+    fn parts(parts: &[(&str, Kind)]) -> Vec<(String, Kind)> {
+        parts
+            .iter()
+            .map(|&(text, kind)| (text.to_string(), kind))
+            .collect()
+    }
+
+    #[test]
+    fn rust_words_and_numbers() {
+        assert_eq!(
+            highlight(Language::Rust, "let x: u32 = 42;"),
+            vec![parts(&[
+                ("let", Kind::Keyword),
+                ("u32", Kind::Type),
+                ("42", Kind::Number)
+            ])]
+        );
+        assert_eq!(
+            highlight(Language::Rust, "fn main() { println!(\"hi\"); } // done"),
+            vec![parts(&[
+                ("fn", Kind::Keyword),
+                ("println!", Kind::Attribute),
+                ("\"hi\"", Kind::String),
+                ("// done", Kind::Comment),
+            ])]
+        );
+        assert_eq!(
+            highlight(Language::Rust, "#[derive(Debug)] struct S;"),
+            vec![parts(&[
+                ("#[derive(Debug)]", Kind::Attribute),
+                ("struct", Kind::Keyword)
+            ])]
+        );
+    }
+
+    #[test]
+    fn rust_lifetimes_are_not_characters() {
+        assert_eq!(
+            highlight(Language::Rust, "fn f<'a>(c: char) -> &'a str { 'x' }"),
+            vec![parts(&[
+                ("fn", Kind::Keyword),
+                ("char", Kind::Type),
+                ("str", Kind::Type),
+                ("'x'", Kind::String),
+            ])]
+        );
+    }
+
+    #[test]
+    fn rust_block_comments_span_lines_and_nest() {
+        let text = "let a = 1; /* one\n/* nested */ still\ncomment */ let";
+        assert_eq!(
+            highlight(Language::Rust, text),
+            vec![
+                parts(&[
+                    ("let", Kind::Keyword),
+                    ("1", Kind::Number),
+                    ("/* one", Kind::Comment)
+                ]),
+                parts(&[("/* nested */ still", Kind::Comment)]),
+                parts(&[("comment */", Kind::Comment), ("let", Kind::Keyword)]),
+            ]
+        );
+    }
+
+    #[test]
+    fn rust_strings_span_lines() {
+        let text = "let s = \"first\nsecond \\\" still\nlast\"; 1";
+        assert_eq!(
+            highlight(Language::Rust, text),
+            vec![
+                parts(&[("let", Kind::Keyword), ("\"first", Kind::String)]),
+                parts(&[("second \\\" still", Kind::String)]),
+                parts(&[("last\"", Kind::String), ("1", Kind::Number)]),
+            ]
+        );
+
+        // Only a quote followed by as many `#`s ends a raw string.
+        let text = "let r = r#\"one \"two\"\nthree\"# + 1;";
+        assert_eq!(
+            highlight(Language::Rust, text),
+            vec![
+                parts(&[("let", Kind::Keyword), ("r#\"one \"two\"", Kind::String)]),
+                parts(&[("three\"#", Kind::String), ("1", Kind::Number)]),
+            ]
+        );
+    }
+
+    #[test]
+    fn toml_keys_strings_and_arrays() {
+        let text = "[package]\n\
+                    name = \"demo\" # the name\n\
+                    description = \"\"\"\n\
+                    multi \"line\"\n\
+                    \"\"\"\n\
+                    deps = [\n  \"a\",\n  \"b\",\n]\n\
+                    version = 1";
+        assert_eq!(
+            highlight(Language::Toml, text),
+            vec![
+                parts(&[("[package]", Kind::Attribute)]),
+                parts(&[
+                    ("name", Kind::Key),
+                    ("\"demo\"", Kind::String),
+                    ("# the name", Kind::Comment)
+                ]),
+                parts(&[("description", Kind::Key), ("\"\"\"", Kind::String)]),
+                parts(&[("multi \"line\"", Kind::String)]),
+                parts(&[("\"\"\"", Kind::String)]),
+                parts(&[("deps", Kind::Key)]),
+                // No keys inside an array, even at the start of a line.
+                parts(&[("\"a\"", Kind::String)]),
+                parts(&[("\"b\"", Kind::String)]),
+                parts(&[]),
+                parts(&[("version", Kind::Key), ("1", Kind::Number)]),
+            ]
+        );
+    }
+
+    #[test]
+    fn json_keys_and_values() {
+        let text = "{\"key\": \"value\", \"n\": -1.5e3, \"ok\": true, \"none\": null}";
+        assert_eq!(
+            highlight(Language::Json, text),
+            vec![parts(&[
+                ("\"key\"", Kind::Key),
+                ("\"value\"", Kind::String),
+                ("\"n\"", Kind::Key),
+                ("-1.5e3", Kind::Number),
+                ("\"ok\"", Kind::Key),
+                ("true", Kind::Keyword),
+                ("\"none\"", Kind::Key),
+                ("null", Kind::Keyword),
+            ])]
+        );
+    }
+
+    #[test]
+    fn markdown_fences_span_lines() {
+        let text = "# Title\n\
+                    Some *emphasis* and `code` with [a link](http://x).\n\
+                    ```rust\n\
+                    let x = 1; // not highlighted as Rust\n\
+                    ```\n\
+                    - item";
+        assert_eq!(
+            highlight(Language::Markdown, text),
+            vec![
+                parts(&[("# Title", Kind::Heading)]),
+                parts(&[
+                    ("*emphasis*", Kind::Emphasis),
+                    ("`code`", Kind::Code),
+                    ("[a link](http://x)", Kind::Link),
+                ]),
+                parts(&[("```rust", Kind::Code)]),
+                parts(&[("let x = 1; // not highlighted as Rust", Kind::Code)]),
+                parts(&[("```", Kind::Code)]),
+                parts(&[("-", Kind::Keyword)]),
+            ]
+        );
+    }
+
+    #[test]
+    fn lines_are_counted_like_the_buffer_does() {
+        assert_eq!(count_lines(""), 0);
+        assert_eq!(count_lines("no break"), 0);
+        assert_eq!(count_lines("a\r\nb\nc\rd\u{2029}"), 4);
+    }
+
+    #[test]
+    fn language_is_detected_from_the_extension() {
+        assert_eq!(
+            Language::detect(Path::new("main.rs"), b""),
+            Some(Language::Rust)
+        );
+        assert_eq!(
+            Language::detect(Path::new("Cargo.toml"), b""),
+            Some(Language::Toml)
+        );
+        assert_eq!(
+            Language::detect(Path::new("README.markdown"), b""),
+            Some(Language::Markdown)
+        );
+    }
+}
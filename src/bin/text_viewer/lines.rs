@@ -0,0 +1,292 @@
+//! Line numbers in a gutter, a highlight on the line holding the cursor and a popover to jump to
+//! a given line and column.
+
+use std::cell::Cell;
+use std::rc::Rc;
+
+use glib::clone;
+use gtk::prelude::*;
+
+const CURRENT_LINE_TAG: &str = "current-line";
+// Space between the line numbers and either edge of the gutter.
+const GUTTER_PADDING: i32 = 6;
+
+/// Draws line numbers into the left border window of `text_view`.
+pub fn show_line_numbers(text_view: &gtk::TextView) {
+    let buffer = text_view.get_buffer().expect("Couldn't get buffer");
+    let line_count = Cell::new(0);
+
+    resize_gutter(text_view, &buffer, &line_count);
+    buffer.connect_changed(clone!(@weak text_view => move |buffer| {
+        resize_gutter(&text_view, buffer, &line_count);
+    }));
+
+    // The text view paints the background of its border windows in its own handler, so the
+    // numbers have to go on top afterwards.
+    text_view
+        .connect_local(
+            "draw",
+            true,
+            clone!(@weak text_view => @default-return None, move |values| {
+                if let Ok(Some(cr)) = values[1].get::<cairo::Context>() {
+                    draw_line_numbers(&text_view, &cr);
+                }
+                Some(false.to_value())
+            }),
+        )
+        .expect("Couldn't connect to draw");
+}
+
+/// Makes the gutter wide enough for the highest line number, once the number of lines changed.
+fn resize_gutter(text_view: &gtk::TextView, buffer: &gtk::TextBuffer, line_count: &Cell<i32>) {
+    let lines = buffer.get_line_count();
+    if lines == line_count.replace(lines) {
+        return;
+    }
+
+    let digits = lines.to_string().len().max(2);
+    let layout = text_view.create_pango_layout(Some(&"0".repeat(digits)));
+    let (width, _) = layout.get_pixel_size();
+    text_view.set_border_window_size(gtk::TextWindowType::Left, width + 2 * GUTTER_PADDING);
+    // Lines further down got a new number.
+    text_view.queue_draw();
+}
+
+fn draw_line_numbers(text_view: &gtk::TextView, cr: &cairo::Context) {
+    let window = match TextViewExt::get_window(text_view, gtk::TextWindowType::Left) {
+        Some(window) => window,
+        None => return,
+    };
+    if !gtk::cairo_should_draw_window(cr, &window) {
+        return;
+    }
+
+    cr.save();
+    gtk::cairo_transform_to_window(cr, text_view, &window);
+
+    let style = text_view.get_style_context();
+    let gutter_width = text_view.get_border_window_size(gtk::TextWindowType::Left);
+    let visible = text_view.get_visible_rect();
+    let (mut line, _) = text_view.get_line_at_y(visible.y);
+
+    loop {
+        let (y, _) = text_view.get_line_yrange(&line);
+        if y > visible.y + visible.height {
+            break;
+        }
+
+        let (_, window_y) = text_view.buffer_to_window_coords(gtk::TextWindowType::Left, 0, y);
+        let layout = text_view.create_pango_layout(Some(&(line.get_line() + 1).to_string()));
+        let (width, _) = layout.get_pixel_size();
+        gtk::render_layout(
+            &style,
+            cr,
+            f64::from(gutter_width - GUTTER_PADDING - width),
+            f64::from(window_y),
+            &layout,
+        );
+
+        if !line.forward_line() {
+            break;
+        }
+    }
+
+    cr.restore();
+}
+
+/// Keeps the background of the line holding the cursor highlighted.
+pub fn highlight_current_line(buffer: &gtk::TextBuffer) {
+    let tag = gtk::TextTag::new(Some(CURRENT_LINE_TAG));
+    tag.set_property_paragraph_background(Some("rgba(128, 128, 128, 0.15)"));
+    buffer
+        .get_tag_table()
+        .expect("Couldn't get tag table")
+        .add(&tag);
+
+    // Where the highlight currently is, so it can be taken off again without going through the
+    // whole buffer. The end moves along with text typed at it, so nothing tagged gets left out.
+    let (start, _) = buffer.get_bounds();
+    let highlight = (
+        buffer
+            .create_mark(None, &start, true)
+            .expect("Couldn't create mark"),
+        buffer
+            .create_mark(None, &start, false)
+            .expect("Couldn't create mark"),
+    );
+
+    // Moving the cursor sets the mark, typing moves it along with the text.
+    buffer.connect_mark_set(clone!(@strong highlight => move |buffer, _, mark| {
+        if buffer.get_insert().as_ref() == Some(mark) {
+            update_current_line(buffer, &highlight);
+        }
+    }));
+    buffer.connect_changed(move |buffer| update_current_line(buffer, &highlight));
+}
+
+fn update_current_line(buffer: &gtk::TextBuffer, highlight: &(gtk::TextMark, gtk::TextMark)) {
+    let (ref start_mark, ref end_mark) = *highlight;
+    let start = buffer.get_iter_at_mark(start_mark);
+    let end = buffer.get_iter_at_mark(end_mark);
+    buffer.remove_tag_by_name(CURRENT_LINE_TAG, &start, &end);
+
+    let cursor = buffer.get_iter_at_mark(&buffer.get_insert().expect("Couldn't get cursor"));
+    let line_start = buffer.get_iter_at_line(cursor.get_line());
+    let mut line_end = line_start.clone();
+    line_end.forward_line();
+    buffer.apply_tag_by_name(CURRENT_LINE_TAG, &line_start, &line_end);
+    buffer.move_mark(start_mark, &line_start);
+    buffer.move_mark(end_mark, &line_end);
+}
+
+/// A popover asking for a line, and optionally a column, to put the cursor on.
+pub struct GoToLine {
+    text_view: gtk::TextView,
+    popover: gtk::Popover,
+    entry: gtk::Entry,
+}
+
+impl GoToLine {
+    pub fn new(text_view: &gtk::TextView) -> Rc<Self> {
+        let popover = gtk::Popover::new(Some(text_view));
+        let entry = gtk::Entry::new();
+        let label = gtk::Label::new(Some("Go to line:"));
+        let hbox = gtk::Box::new(gtk::Orientation::Horizontal, 6);
+
+        popover.set_position(gtk::PositionType::Bottom);
+        entry.set_placeholder_text(Some("line[:column]"));
+        entry.set_width_chars(12);
+        hbox.set_border_width(6);
+        hbox.pack_start(&label, false, false, 0);
+        hbox.pack_start(&entry, true, true, 0);
+        popover.add(&hbox);
+        hbox.show_all();
+
+        let go_to_line = Rc::new(GoToLine {
+            text_view: text_view.clone(),
+            popover,
+            entry,
+        });
+
+        go_to_line
+            .entry
+            .connect_activate(clone!(@weak go_to_line => move |_| {
+                go_to_line.go();
+            }));
+        go_to_line.entry.connect_changed(|entry| {
+            entry.get_style_context().remove_class("error");
+        });
+        go_to_line
+            .popover
+            .connect_closed(clone!(@weak go_to_line => move |_| {
+                go_to_line.text_view.grab_focus();
+            }));
+
+        go_to_line
+    }
+
+    /// Opens the popover at the top of the text view, starting off with where the cursor is.
+    pub fn show(&self) {
+        let buffer = self.text_view.get_buffer().expect("Couldn't get buffer");
+        let cursor = buffer.get_iter_at_mark(&buffer.get_insert().expect("Couldn't get cursor"));
+        self.entry.set_text(&format!(
+            "{}:{}",
+            cursor.get_line() + 1,
+            cursor.get_line_offset() + 1
+        ));
+
+        let width = self.text_view.get_allocated_width();
+        self.popover.set_pointing_to(&gtk::Rectangle {
+            x: width / 2,
+            y: 0,
+            width: 1,
+            height: 1,
+        });
+        self.popover.show();
+        self.entry.grab_focus();
+    }
+
+    fn go(&self) {
+        let (line, column) = match parse_position(&self.entry.get_text()) {
+            Some(position) => position,
+            None => {
+                self.entry.get_style_context().add_class("error");
+                return;
+            }
+        };
+
+        let buffer = self.text_view.get_buffer().expect("Couldn't get buffer");
+        let line = (line - 1).min(buffer.get_line_count() - 1);
+        let mut position = buffer.get_iter_at_line(line);
+        let mut line_end = position.clone();
+        if !line_end.ends_line() {
+            line_end.forward_to_line_end();
+        }
+        position.set_line_offset((column - 1).min(line_end.get_line_offset()));
+
+        buffer.place_cursor(&position);
+        // A bit above the middle, so what led up to the line is in view too.
+        self.text_view.scroll_to_mark(
+            &buffer.get_insert().expect("Couldn't get cursor"),
+            0.0,
+            true,
+            0.0,
+            0.3,
+        );
+        self.popover.hide();
+    }
+}
+
+/// Parses "line" or "line:column", both counting from 1.
+fn parse_position(text: &str) -> Option<(i32, i32)> {
+    let mut parts = text.trim().splitn(2, ':');
+    let line = parts.next()?.trim().parse().ok().filter(|&line| line > 0)?;
+    let column = match parts.next() {
+        Some(column) => column.trim().parse().ok().filter(|&column| column > 0)?,
+        None => 1,
+    };
+    Some((line, column))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lines_and_columns() {
+        assert_eq!(parse_position("12"), Some((12, 1)));
+        assert_eq!(parse_position("12:7"), Some((12, 7)));
+        assert_eq!(parse_position("1:1"), Some((1, 1)));
+    }
+
+    #[test]
+    fn counting_starts_at_1() {
+        assert_eq!(parse_position("0"), None);
+        assert_eq!(parse_position("0:5"), None);
+        assert_eq!(parse_position("5:0"), None);
+    }
+
+    #[test]
+    fn numbers_out_of_range_are_rejected() {
+        assert_eq!(parse_position("-3"), None);
+        assert_eq!(parse_position("3:-1"), None);
+        assert_eq!(parse_position("2147483648"), None);
+        assert_eq!(parse_position("1:99999999999"), None);
+    }
+
+    #[test]
+    fn whitespace_around_the_numbers_is_ignored() {
+        assert_eq!(parse_position("  12  "), Some((12, 1)));
+        assert_eq!(parse_position(" 12 : 7 "), Some((12, 7)));
+        assert_eq!(parse_position("1 2"), None);
+    }
+
+    #[test]
+    fn empty_input_is_rejected() {
+        assert_eq!(parse_position(""), None);
+        assert_eq!(parse_position("   "), None);
+        assert_eq!(parse_position(":"), None);
+        assert_eq!(parse_position("12:"), None);
+        assert_eq!(parse_position(":7"), None);
+    }
+}
@@ -0,0 +1,545 @@
+//! # Toolbar, Scrollable Text View and File Chooser
+//!
+//! A simple text file viewer. Edits can be written back with Save / Save As, the window title
+//! shows whether the buffer has unsaved changes and the user is asked before they get lost.
+//!
+//! Files are streamed into the buffer asynchronously with `gio`, so even huge ones keep the UI
+//! responsive and can be cancelled half-way. The character encoding is either picked in the file
+//! chooser or detected from the file itself, and is kept when the file is saved again.
+//!
+//! Ctrl+F and Ctrl+H open a find & replace bar supporting whole words and regular expressions.
+//!
+//! Rust, TOML, JSON and Markdown files are syntax highlighted, based on their extension or on the
+//! content type guessed from the file.
+//!
+//! Edits can be undone with Ctrl+Z and redone with Ctrl+Shift+Z, see `src/undo.rs`.
+//!
+//! Line numbers are drawn in a gutter next to the text, the line with the cursor is highlighted
+//! and Ctrl+L jumps to a line, or to a column on it with `line:column`.
+//!
+//! Every file gets its own tab. Tabs can be dragged around, switched with Ctrl+Tab and closed
+//! from their context menu, and opening a file that is already open just brings its tab up.
+//!
+//! Files given on the command line are opened straight away. If the viewer is already running,
+//! they are handed over to that instance through the application's D-Bus name instead, so
+//! `text_viewer a.txt b.txt` adds tabs to the existing window rather than opening another one.
+//!
+//! Each open file is watched for changes made by other programs, offering to reload it. In follow
+//! mode whatever gets appended to it shows up at the end of the buffer, like `tail -f`.
+
+extern crate cairo;
+extern crate gio;
+extern crate glib;
+extern crate glib_sys;
+extern crate gtk;
+extern crate pango;
+
+use std::cell::{Cell, RefCell};
+use std::env::args;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::rc::{Rc, Weak};
+use std::str;
+
+use gio::prelude::*;
+use glib::clone;
+use gtk::prelude::*;
+use gtk::Builder;
+
+mod document;
+mod encoding;
+mod highlight;
+mod lines;
+mod notebook;
+mod regex;
+mod search;
+
+use document::Document;
+use encoding::{encoding_selector, Encoding};
+use notebook::Notebook;
+use search::Search;
+
+const APP_TITLE: &str = "Text File Viewer";
+
+// Big enough to get through large files quickly, small enough to keep every step short.
+const CHUNK_SIZE: usize = 64 * 1024;
+
+pub struct TextViewer {
+    window: gtk::ApplicationWindow,
+    notebook: Notebook,
+    search: Rc<Search>,
+    follow_button: gtk::ToggleToolButton,
+    documents: RefCell<Vec<Rc<Document>>>,
+    /// Set while the toolbar catches up with a newly selected tab.
+    switching: Cell<bool>,
+}
+
+impl TextViewer {
+    fn new(builder: &Builder) -> Rc<Self> {
+        let window: gtk::ApplicationWindow =
+            builder.get_object("window").expect("Couldn't get window");
+        let v_box: gtk::Box = builder.get_object("v_box").expect("Couldn't get v_box");
+        let follow_button: gtk::ToggleToolButton = builder
+            .get_object("follow_button")
+            .expect("Couldn't get follow_button");
+
+        let notebook = Notebook::new();
+        v_box.pack_start(&notebook.notebook, true, true, 0);
+
+        let viewer = Rc::new(TextViewer {
+            window,
+            notebook,
+            search: Rc::new(Search::new(builder)),
+            follow_button,
+            documents: RefCell::new(Vec::new()),
+            switching: Cell::new(false),
+        });
+
+        viewer
+            .notebook
+            .notebook
+            .connect_switch_page(clone!(@weak viewer => move |_, page, _| {
+                // The notebook still reports the old page as the current one at this point.
+                if let Some(document) = viewer.document_for(page) {
+                    viewer.selected(&document);
+                }
+            }));
+        viewer
+            .follow_button
+            .connect_toggled(clone!(@weak viewer => move |follow_button| {
+                if !viewer.switching.get() {
+                    viewer.current().set_following(follow_button.get_active());
+                }
+            }));
+        viewer.connect_search();
+        viewer.add_document();
+
+        viewer
+    }
+
+    fn connect_search(self: &Rc<Self>) {
+        let search = &self.search;
+
+        // Only fires once typing paused, the whole buffer gets searched every time
+        search
+            .entry
+            .connect_search_changed(clone!(@weak self as viewer => move |_| {
+                viewer.search.update(&viewer.current().buffer);
+            }));
+        search
+            .entry
+            .connect_activate(clone!(@weak self as viewer => move |_| {
+                viewer.search.find(&viewer.current().text_view, false);
+            }));
+        for toggle in &[&search.match_case, &search.whole_word, &search.use_regex] {
+            toggle.connect_toggled(clone!(@weak self as viewer => move |_| {
+                viewer.search.update(&viewer.current().buffer);
+            }));
+        }
+        search
+            .previous_button
+            .connect_clicked(clone!(@weak self as viewer => move |_| {
+                viewer.search.find(&viewer.current().text_view, true);
+            }));
+        search
+            .next_button
+            .connect_clicked(clone!(@weak self as viewer => move |_| {
+                viewer.search.find(&viewer.current().text_view, false);
+            }));
+        search
+            .replace_entry
+            .connect_activate(clone!(@weak self as viewer => move |_| {
+                viewer.search.replace(&viewer.current().text_view);
+            }));
+        search
+            .replace_button
+            .connect_clicked(clone!(@weak self as viewer => move |_| {
+                viewer.search.replace(&viewer.current().text_view);
+            }));
+        search
+            .replace_all_button
+            .connect_clicked(clone!(@weak self as viewer => move |_| {
+                viewer.search.replace_all(&viewer.current().buffer);
+            }));
+        search.bar.connect_property_search_mode_enabled_notify(
+            clone!(@weak self as viewer => move |bar| {
+                if !bar.get_search_mode() {
+                    let document = viewer.current();
+                    viewer.search.clear(&document.buffer);
+                    document.text_view.grab_focus();
+                }
+            }),
+        );
+    }
+
+    fn document_for(&self, page: &gtk::Widget) -> Option<Rc<Document>> {
+        self.documents
+            .borrow()
+            .iter()
+            .find(|document| &document.page.clone().upcast::<gtk::Widget>() == page)
+            .cloned()
+    }
+
+    /// The document in the selected tab. There always is one.
+    fn current(&self) -> Rc<Document> {
+        let notebook = &self.notebook.notebook;
+        notebook
+            .get_nth_page(notebook.get_current_page())
+            .and_then(|page| self.document_for(&page))
+            .expect("Couldn't get current document")
+    }
+
+    /// Brings the toolbar and the title in line with the newly selected `document`.
+    fn selected(&self, document: &Document) {
+        self.switching.set(true);
+        self.follow_button.set_active(document.is_following());
+        self.switching.set(false);
+        // Replaces the actions of the previous tab, which go by the same names.
+        document.undo.add_actions(&self.window);
+        document.present();
+    }
+
+    fn focus(&self, document: &Document) {
+        let notebook = &self.notebook.notebook;
+        notebook.set_current_page(notebook.page_num(&document.page));
+        document.text_view.grab_focus();
+    }
+
+    /// Adds a tab with an empty document and selects it.
+    fn add_document(self: &Rc<Self>) -> Rc<Document> {
+        let document = Document::new(&self.window, &self.notebook, self.search.clone());
+
+        document.tab.close_button.connect_clicked(
+            clone!(@weak self as viewer, @weak document => move |_| {
+                viewer.close(&document, |_| {});
+            }),
+        );
+        document.tab.header.connect_button_press_event(
+            clone!(@weak self as viewer, @weak document => @default-return Inhibit(false), move |_, event| {
+                if event.get_button() != 3 {
+                    return Inhibit(false);
+                }
+
+                let menu = gtk::Menu::new();
+                let close_others = gtk::MenuItem::with_label("Close Other Tabs");
+                close_others.set_sensitive(viewer.documents.borrow().len() > 1);
+                close_others.connect_activate(clone!(@weak viewer, @weak document => move |_| {
+                    viewer.close_others(&document);
+                }));
+                menu.append(&close_others);
+                menu.show_all();
+                menu.popup_easy(event.get_button(), event.get_time());
+                Inhibit(true)
+            }),
+        );
+
+        self.documents.borrow_mut().push(document.clone());
+        self.focus(&document);
+        // The first tab of a notebook is selected before it is known as a document.
+        if self.documents.borrow().len() == 1 {
+            self.selected(&document);
+        }
+
+        document
+    }
+
+    fn open(self: &Rc<Self>) {
+        let file_chooser = gtk::FileChooserDialog::new(
+            Some("Open File"),
+            Some(&self.window),
+            gtk::FileChooserAction::Open,
+        );
+        file_chooser.add_buttons(&[
+            ("Open", gtk::ResponseType::Ok),
+            ("Cancel", gtk::ResponseType::Cancel),
+        ]);
+        file_chooser.set_select_multiple(true);
+        let (selector, encodings) = encoding_selector(true);
+        encodings.set_active_id(Some("auto"));
+        file_chooser.set_extra_widget(&selector);
+
+        file_chooser.connect_response(
+            clone!(@weak self as viewer => move |file_chooser, response| {
+                if response == gtk::ResponseType::Ok {
+                    let charset = encodings
+                        .get_active_id()
+                        .and_then(|id| Encoding::from_charset(&id));
+                    for filename in file_chooser.get_filenames() {
+                        viewer.open_path(filename, charset);
+                    }
+                }
+                file_chooser.close();
+            }),
+        );
+
+        file_chooser.show_all();
+    }
+
+    /// Opens `path` in a tab of its own, unless it is open already.
+    fn open_path(self: &Rc<Self>, path: PathBuf, charset: Option<&'static str>) {
+        let open = self.documents.borrow().iter().find_map(|document| {
+            document
+                .path()
+                .filter(|open| same_file(open, &path))
+                .map(|_| document.clone())
+        });
+        if let Some(document) = open {
+            return self.focus(&document);
+        }
+
+        // A blank tab gets reused rather than left behind.
+        let current = self.current();
+        let document = if current.is_blank() {
+            current
+        } else {
+            self.add_document()
+        };
+        document.load(path, charset);
+        self.focus(&document);
+    }
+
+    /// Closes the tab of `document` unless the user would rather keep its changes after all,
+    /// then runs `then`. A window without tabs gets a blank one.
+    fn close<F: Fn(&Rc<Self>) + Clone + 'static>(
+        self: &Rc<Self>,
+        document: &Rc<Document>,
+        then: F,
+    ) {
+        self.focus(document);
+        document.confirm_discard(clone!(@weak self as viewer => move |document| {
+            document.cancel_loading();
+            document.unwatch();
+            viewer.documents.borrow_mut().retain(|open| !Rc::ptr_eq(open, document));
+            let notebook = &viewer.notebook.notebook;
+            notebook.remove_page(notebook.page_num(&document.page));
+            if viewer.documents.borrow().is_empty() {
+                viewer.add_document();
+            }
+            then(&viewer);
+        }));
+    }
+
+    /// Closes `documents` one after the other, stopping as soon as the user cancels.
+    fn close_all<F: Fn(&Rc<Self>) + Clone + 'static>(
+        self: &Rc<Self>,
+        mut documents: Vec<Rc<Document>>,
+        then: F,
+    ) {
+        let document = match documents.pop() {
+            Some(document) => document,
+            None => return then(self),
+        };
+        self.close(&document, move |viewer| {
+            viewer.close_all(documents.clone(), then.clone());
+        });
+    }
+
+    fn close_others(self: &Rc<Self>, keep: &Rc<Document>) {
+        let others = self
+            .documents
+            .borrow()
+            .iter()
+            .filter(|document| !Rc::ptr_eq(document, keep))
+            .cloned()
+            .collect();
+        self.close_all(
+            others,
+            clone!(@weak keep => move |viewer| viewer.focus(&keep)),
+        );
+    }
+
+    /// Selects the next tab, or the previous one, going round at either end.
+    fn cycle_tabs(&self, backwards: bool) {
+        let notebook = &self.notebook.notebook;
+        let pages = notebook.get_n_pages();
+        let current = notebook.get_current_page().unwrap_or(0);
+        let next = if backwards {
+            (current + pages - 1) % pages
+        } else {
+            (current + 1) % pages
+        };
+        notebook.set_current_page(Some(next));
+    }
+}
+
+/// Whether `a` and `b` lead to the same file, even if they were spelled differently.
+fn same_file(a: &Path, b: &Path) -> bool {
+    match (fs::canonicalize(a), fs::canonicalize(b)) {
+        (Ok(a), Ok(b)) => a == b,
+        _ => a == b,
+    }
+}
+
+fn add_actions(application: &gtk::Application, viewer: &Rc<TextViewer>) {
+    let save = gio::SimpleAction::new("save", None);
+    save.connect_activate(clone!(@weak viewer => move |_, _| {
+        viewer.current().save(|_| {});
+    }));
+
+    let save_as = gio::SimpleAction::new("save-as", None);
+    save_as.connect_activate(clone!(@weak viewer => move |_, _| {
+        viewer.current().save_as(|_| {});
+    }));
+
+    let find = gio::SimpleAction::new("find", None);
+    find.connect_activate(clone!(@weak viewer => move |_, _| {
+        viewer.search.show(&viewer.current().text_view, false);
+    }));
+
+    let replace = gio::SimpleAction::new("replace", None);
+    replace.connect_activate(clone!(@weak viewer => move |_, _| {
+        viewer.search.show(&viewer.current().text_view, true);
+    }));
+
+    let find_next = gio::SimpleAction::new("find-next", None);
+    find_next.connect_activate(clone!(@weak viewer => move |_, _| {
+        viewer.search.find(&viewer.current().text_view, false);
+    }));
+
+    let find_previous = gio::SimpleAction::new("find-previous", None);
+    find_previous.connect_activate(clone!(@weak viewer => move |_, _| {
+        viewer.search.find(&viewer.current().text_view, true);
+    }));
+
+    let go_to_line = gio::SimpleAction::new("go-to-line", None);
+    go_to_line.connect_activate(clone!(@weak viewer => move |_, _| {
+        viewer.current().go_to_line.show();
+    }));
+
+    let close_tab = gio::SimpleAction::new("close-tab", None);
+    close_tab.connect_activate(clone!(@weak viewer => move |_, _| {
+        viewer.close(&viewer.current(), |_| {});
+    }));
+
+    let close_others = gio::SimpleAction::new("close-others", None);
+    close_others.connect_activate(clone!(@weak viewer => move |_, _| {
+        viewer.close_others(&viewer.current());
+    }));
+
+    let next_tab = gio::SimpleAction::new("next-tab", None);
+    next_tab.connect_activate(clone!(@weak viewer => move |_, _| {
+        viewer.cycle_tabs(false);
+    }));
+
+    let previous_tab = gio::SimpleAction::new("previous-tab", None);
+    previous_tab.connect_activate(clone!(@weak viewer => move |_, _| {
+        viewer.cycle_tabs(true);
+    }));
+
+    application.add_action(&save);
+    application.add_action(&save_as);
+    application.add_action(&find);
+    application.add_action(&replace);
+    application.add_action(&find_next);
+    application.add_action(&find_previous);
+    application.add_action(&go_to_line);
+    application.add_action(&close_tab);
+    application.add_action(&close_others);
+    application.add_action(&next_tab);
+    application.add_action(&previous_tab);
+}
+
+fn add_accelerators(application: &gtk::Application) {
+    application.set_accels_for_action("app.save", &["<Primary>S"]);
+    application.set_accels_for_action("app.save-as", &["<Primary><Shift>S"]);
+    application.set_accels_for_action("app.find", &["<Primary>F"]);
+    application.set_accels_for_action("app.replace", &["<Primary>H"]);
+    application.set_accels_for_action("app.find-next", &["<Primary>G"]);
+    application.set_accels_for_action("app.find-previous", &["<Primary><Shift>G"]);
+    application.set_accels_for_action("app.go-to-line", &["<Primary>L"]);
+    application.set_accels_for_action("win.undo", &["<Primary>Z"]);
+    application.set_accels_for_action("win.redo", &["<Primary><Shift>Z"]);
+    application.set_accels_for_action("app.close-tab", &["<Primary>W"]);
+    application.set_accels_for_action("app.next-tab", &["<Primary>Tab"]);
+    // Shift turns Tab into ISO_Left_Tab, so both spellings are needed to catch it.
+    application.set_accels_for_action(
+        "app.previous-tab",
+        &["<Primary><Shift>Tab", "<Primary><Shift>ISO_Left_Tab"],
+    );
+}
+
+pub fn build_ui(application: &gtk::Application) -> Rc<TextViewer> {
+    let glade_src = include_str!("text_viewer.glade");
+    let builder = Builder::new();
+    builder
+        .add_from_string(glade_src)
+        .expect("Couldn't add from string");
+
+    let viewer = TextViewer::new(&builder);
+    let window = viewer.window.clone();
+    window.set_application(Some(application));
+    let open_button: gtk::ToolButton = builder
+        .get_object("open_button")
+        .expect("Couldn't get builder");
+
+    open_button.connect_clicked(clone!(@weak viewer => move |_| {
+        viewer.open();
+    }));
+
+    // The window owns the viewer: once it is gone, so are the handlers below.
+    window.connect_delete_event(clone!(@strong viewer => move |_, _| {
+        let documents = viewer.documents.borrow().clone();
+        if documents.iter().all(|document| !document.buffer.get_modified()) {
+            for document in &documents {
+                document.cancel_loading();
+            }
+            return Inhibit(false);
+        }
+        // Whatever is left once every tab was either saved or thrown away won't ask again.
+        viewer.close_all(documents, |viewer| viewer.window.close());
+        Inhibit(true)
+    }));
+
+    add_actions(application, &viewer);
+
+    window.show_all();
+
+    viewer
+}
+
+/// The `app.*` actions act on a single window, so this only builds one if there is none yet.
+fn get_viewer(
+    application: &gtk::Application,
+    viewer: &RefCell<Weak<TextViewer>>,
+) -> Rc<TextViewer> {
+    if let Some(viewer) = viewer.borrow().upgrade() {
+        return viewer;
+    }
+
+    let new_viewer = build_ui(application);
+    *viewer.borrow_mut() = Rc::downgrade(&new_viewer);
+    new_viewer
+}
+
+fn main() {
+    let application = gtk::Application::new(
+        Some("com.github.gtk-rs.examples.text_viewer"),
+        gio::ApplicationFlags::HANDLES_OPEN,
+    )
+    .expect("Initialization failed...");
+
+    application.connect_startup(|app| {
+        add_accelerators(app);
+    });
+    // The window keeps the viewer alive, this only remembers where to find it.
+    let viewer = Rc::new(RefCell::new(Weak::new()));
+
+    application.connect_activate(clone!(@strong viewer => move |app| {
+        get_viewer(app, &viewer).window.present();
+    }));
+    // Runs in the primary instance, also for files passed to any later invocation.
+    application.connect_open(clone!(@strong viewer => move |app, files, _| {
+        let viewer = get_viewer(app, &viewer);
+        for file in files {
+            match file.get_path() {
+                Some(path) => viewer.open_path(path, None),
+                None => viewer
+                    .current()
+                    .show_error(&format!("Can't open {}: not a local file", file.get_uri())),
+            }
+        }
+        viewer.window.present();
+    }));
+
+    application.run(&args().collect::<Vec<_>>());
+}
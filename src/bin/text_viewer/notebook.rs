@@ -0,0 +1,55 @@
+//! The notebook with closable tabs from the notebook example.
+//!
+//! Closing a tab is left to the caller here, since a document with unsaved changes has to ask
+//! first. Tabs can be dragged around to reorder them.
+
+use gtk::prelude::*;
+use gtk::{IconSize, Orientation, ReliefStyle};
+
+/// The widgets making up a tab.
+pub struct Tab {
+    /// Wraps the tab so it gets the clicks, for the context menu.
+    pub header: gtk::EventBox,
+    pub label: gtk::Label,
+    pub close_button: gtk::Button,
+}
+
+pub struct Notebook {
+    pub notebook: gtk::Notebook,
+}
+
+impl Notebook {
+    pub fn new() -> Notebook {
+        let notebook = gtk::Notebook::new();
+        notebook.set_scrollable(true);
+
+        Notebook { notebook }
+    }
+
+    pub fn create_tab<W: IsA<gtk::Widget>>(&self, title: &str, widget: &W) -> Tab {
+        let close_image = gtk::Image::from_icon_name(Some("window-close"), IconSize::Button);
+        let close_button = gtk::Button::new();
+        let label = gtk::Label::new(Some(title));
+        let tab = gtk::Box::new(Orientation::Horizontal, 0);
+        let header = gtk::EventBox::new();
+
+        close_button.set_relief(ReliefStyle::None);
+        close_button.set_focus_on_click(false);
+        close_button.add(&close_image);
+
+        tab.pack_start(&label, false, false, 0);
+        tab.pack_start(&close_button, false, false, 0);
+        header.set_visible_window(false);
+        header.add(&tab);
+        header.show_all();
+
+        self.notebook.append_page(widget, Some(&header));
+        self.notebook.set_tab_reorderable(widget, true);
+
+        Tab {
+            header,
+            label,
+            close_button,
+        }
+    }
+}
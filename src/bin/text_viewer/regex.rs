@@ -0,0 +1,119 @@
+//! The bits of GLib's `GRegex` needed for searching, which the `glib` crate doesn't bind yet.
+
+use std::ffi::CString;
+use std::ops::Range;
+use std::os::raw::c_char;
+use std::ptr;
+
+use glib::translate::*;
+
+pub struct Regex(ptr::NonNull<glib_sys::GRegex>);
+
+pub struct RegexMatch {
+    /// Byte range of the match.
+    pub range: Range<usize>,
+    /// The replacement asked for, with its back references expanded for this match.
+    pub replacement: Option<String>,
+}
+
+impl Regex {
+    /// Compiles `pattern` so that `^` and `$` match at every line.
+    pub fn new(pattern: &str, case_sensitive: bool) -> Result<Self, glib::Error> {
+        let pattern = CString::new(pattern).expect("Patterns come from an entry, without nuls");
+        let mut flags = glib_sys::G_REGEX_MULTILINE | glib_sys::G_REGEX_OPTIMIZE;
+        if !case_sensitive {
+            flags |= glib_sys::G_REGEX_CASELESS;
+        }
+
+        unsafe {
+            let mut error = ptr::null_mut();
+            let regex = glib_sys::g_regex_new(pattern.as_ptr(), flags, 0, &mut error);
+            match ptr::NonNull::new(regex) {
+                Some(regex) => Ok(Regex(regex)),
+                None => Err(from_glib_full(error)),
+            }
+        }
+    }
+
+    /// Escapes `text` so that it only matches itself.
+    pub fn escape(text: &str) -> String {
+        unsafe {
+            from_glib_full(glib_sys::g_regex_escape_string(
+                text.to_glib_none().0,
+                text.len() as i32,
+            ))
+        }
+    }
+
+    /// Returns the byte ranges of all matches in `text`. If a `replacement` is given, it is
+    /// returned along with each match, with its back references (`\0`, `\1`, ...) expanded.
+    pub fn find_all(
+        &self,
+        text: &str,
+        replacement: Option<&str>,
+    ) -> Result<Vec<RegexMatch>, glib::Error> {
+        let replacement = replacement
+            .map(|replacement| CString::new(replacement).expect("Replacements come from an entry"));
+        let mut matches = Vec::new();
+
+        unsafe {
+            let mut match_info = ptr::null_mut();
+            let mut error = ptr::null_mut();
+            glib_sys::g_regex_match_full(
+                self.0.as_ptr(),
+                text.as_ptr() as *const c_char,
+                text.len() as isize,
+                0,
+                0,
+                &mut match_info,
+                &mut error,
+            );
+
+            while error.is_null() && glib_sys::g_match_info_matches(match_info) != glib_sys::GFALSE
+            {
+                let (mut start, mut end) = (0, 0);
+                glib_sys::g_match_info_fetch_pos(match_info, 0, &mut start, &mut end);
+
+                let replacement = match replacement {
+                    Some(ref replacement) => {
+                        let expanded = glib_sys::g_match_info_expand_references(
+                            match_info,
+                            replacement.as_ptr(),
+                            &mut error,
+                        );
+                        if !error.is_null() {
+                            break;
+                        }
+                        // GLib hands back NULL rather than an empty string.
+                        Some(if expanded.is_null() {
+                            String::new()
+                        } else {
+                            from_glib_full(expanded)
+                        })
+                    }
+                    None => None,
+                };
+                matches.push(RegexMatch {
+                    range: start as usize..end as usize,
+                    replacement,
+                });
+
+                glib_sys::g_match_info_next(match_info, &mut error);
+            }
+
+            glib_sys::g_match_info_free(match_info);
+
+            if error.is_null() {
+                Ok(matches)
+            } else {
+                Err(from_glib_full(error))
+            }
+        }
+    }
+}
+
+impl Drop for Regex {
+    fn drop(&mut self) {
+        unsafe { glib_sys::g_regex_unref(self.0.as_ptr()) }
+    }
+}
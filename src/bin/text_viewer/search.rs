@@ -0,0 +1,338 @@
+//! The find & replace bar.
+//!
+//! Every mode boils down to a `GRegex`: plain text is escaped, whole words get wrapped in `\b`
+//! and case sensitivity is a compile flag. Matches are highlighted with a `TextTag`.
+
+use gtk::prelude::*;
+use gtk::Builder;
+
+use crate::regex::Regex;
+
+const MATCH_TAG: &str = "search-match";
+// How long typing has to pause before the buffer is searched again, like `GtkSearchEntry`
+// waits before emitting `search-changed`.
+pub const SEARCH_DELAY_MS: u32 = 150;
+
+/// A match as character offsets into the buffer, the unit `TextIter`s work with.
+struct Match {
+    start: i32,
+    end: i32,
+    replacement: Option<String>,
+}
+
+pub struct Search {
+    pub bar: gtk::SearchBar,
+    pub entry: gtk::SearchEntry,
+    pub replace_entry: gtk::Entry,
+    pub match_case: gtk::ToggleButton,
+    pub whole_word: gtk::ToggleButton,
+    pub use_regex: gtk::ToggleButton,
+    pub previous_button: gtk::Button,
+    pub next_button: gtk::Button,
+    pub replace_button: gtk::Button,
+    pub replace_all_button: gtk::Button,
+    replace_box: gtk::Box,
+    status: gtk::Label,
+}
+
+impl Search {
+    pub fn new(builder: &Builder) -> Self {
+        let bar: gtk::SearchBar = builder
+            .get_object("search_bar")
+            .expect("Couldn't get search_bar");
+        let entry: gtk::SearchEntry = builder
+            .get_object("search_entry")
+            .expect("Couldn't get search_entry");
+        let replace_entry: gtk::Entry = builder
+            .get_object("replace_entry")
+            .expect("Couldn't get replace_entry");
+        let match_case: gtk::ToggleButton = builder
+            .get_object("match_case_button")
+            .expect("Couldn't get match_case_button");
+        let whole_word: gtk::ToggleButton = builder
+            .get_object("whole_word_button")
+            .expect("Couldn't get whole_word_button");
+        let use_regex: gtk::ToggleButton = builder
+            .get_object("regex_button")
+            .expect("Couldn't get regex_button");
+        let previous_button: gtk::Button = builder
+            .get_object("previous_button")
+            .expect("Couldn't get previous_button");
+        let next_button: gtk::Button = builder
+            .get_object("next_button")
+            .expect("Couldn't get next_button");
+        let replace_button: gtk::Button = builder
+            .get_object("replace_button")
+            .expect("Couldn't get replace_button");
+        let replace_all_button: gtk::Button = builder
+            .get_object("replace_all_button")
+            .expect("Couldn't get replace_all_button");
+        let replace_box: gtk::Box = builder
+            .get_object("replace_box")
+            .expect("Couldn't get replace_box");
+        let status: gtk::Label = builder
+            .get_object("search_status")
+            .expect("Couldn't get search_status");
+
+        let search = Search {
+            bar,
+            entry,
+            replace_entry,
+            match_case,
+            whole_word,
+            use_regex,
+            previous_button,
+            next_button,
+            replace_button,
+            replace_all_button,
+            replace_box,
+            status,
+        };
+        // Lets the bar close itself on Escape.
+        search.bar.connect_entry(&search.entry);
+
+        search
+    }
+
+    pub fn is_active(&self) -> bool {
+        self.bar.get_search_mode()
+    }
+
+    /// Opens the bar, with the replace row if asked for, and starts off with the selected text.
+    pub fn show(&self, text_view: &gtk::TextView, with_replace: bool) {
+        let buffer = text_view.get_buffer().expect("Couldn't get buffer");
+        if let Some((start, end)) = buffer.get_selection_bounds() {
+            if start.get_line() == end.get_line() {
+                if let Some(selected) = buffer.get_text(&start, &end, false) {
+                    self.entry.set_text(&selected);
+                }
+            }
+        }
+
+        self.replace_box.set_visible(with_replace);
+        self.bar.set_search_mode(true);
+        self.entry.grab_focus();
+        self.update(&buffer);
+    }
+
+    fn compile(&self) -> Result<Option<Regex>, glib::Error> {
+        let text = self.entry.get_text();
+        if text.is_empty() {
+            return Ok(None);
+        }
+
+        let mut pattern = if self.use_regex.get_active() {
+            text.to_string()
+        } else {
+            Regex::escape(&text)
+        };
+        if self.whole_word.get_active() {
+            pattern = format!("\\b(?:{})\\b", pattern);
+        }
+
+        Regex::new(&pattern, self.match_case.get_active()).map(Some)
+    }
+
+    /// Finds all matches in `buffer`, along with what each would be replaced with if
+    /// `with_replacement` is set. Replacements only expand back references in regex mode.
+    fn find_matches(
+        &self,
+        buffer: &gtk::TextBuffer,
+        with_replacement: bool,
+    ) -> Result<Vec<Match>, glib::Error> {
+        let regex = match self.compile()? {
+            Some(regex) => regex,
+            None => return Ok(Vec::new()),
+        };
+
+        let (start, end) = buffer.get_bounds();
+        let text = buffer
+            .get_slice(&start, &end, true)
+            .expect("Couldn't get text");
+        let replacement = self.replace_entry.get_text();
+        let expand = if with_replacement && self.use_regex.get_active() {
+            Some(replacement.as_str())
+        } else {
+            None
+        };
+        let found = regex.find_all(&text, expand)?;
+
+        // The ranges come sorted, so byte offsets can be turned into character offsets in a
+        // single pass.
+        let (mut bytes, mut chars) = (0, 0);
+        let mut char_offset = |byte: usize| {
+            chars += text[bytes..byte].chars().count() as i32;
+            bytes = byte;
+            chars
+        };
+
+        Ok(found
+            .into_iter()
+            .map(|found| Match {
+                start: char_offset(found.range.start),
+                end: char_offset(found.range.end),
+                replacement: if with_replacement {
+                    Some(found.replacement.unwrap_or_else(|| replacement.to_string()))
+                } else {
+                    None
+                },
+            })
+            .collect())
+    }
+
+    fn match_tag(buffer: &gtk::TextBuffer) -> gtk::TextTag {
+        let tag_table = buffer.get_tag_table().expect("Couldn't get tag table");
+        if let Some(tag) = tag_table.lookup(MATCH_TAG) {
+            return tag;
+        }
+
+        let tag = gtk::TextTag::new(Some(MATCH_TAG));
+        tag.set_property_background(Some("yellow"));
+        tag.set_property_foreground(Some("black"));
+        tag_table.add(&tag);
+        tag
+    }
+
+    fn set_status(&self, status: &str, error: Option<&glib::Error>) {
+        self.status.set_text(status);
+
+        let style = self.entry.get_style_context();
+        match error {
+            Some(error) => {
+                style.add_class("error");
+                self.entry.set_tooltip_text(Some(&error.to_string()));
+            }
+            None => {
+                style.remove_class("error");
+                self.entry.set_tooltip_text(None);
+            }
+        }
+    }
+
+    pub fn clear(&self, buffer: &gtk::TextBuffer) {
+        let (start, end) = buffer.get_bounds();
+        buffer.remove_tag(&Self::match_tag(buffer), &start, &end);
+        self.set_status("", None);
+    }
+
+    /// Highlights all matches in `buffer`.
+    pub fn update(&self, buffer: &gtk::TextBuffer) {
+        self.clear(buffer);
+
+        let matches = match self.find_matches(buffer, false) {
+            Ok(matches) => matches,
+            Err(err) => return self.set_status("Invalid pattern", Some(&err)),
+        };
+
+        let tag = Self::match_tag(buffer);
+        for m in &matches {
+            buffer.apply_tag(
+                &tag,
+                &buffer.get_iter_at_offset(m.start),
+                &buffer.get_iter_at_offset(m.end),
+            );
+        }
+
+        match matches.len() {
+            0 if self.entry.get_text().is_empty() => self.set_status("", None),
+            0 => self.set_status("No matches", None),
+            1 => self.set_status("1 match", None),
+            n => self.set_status(&format!("{} matches", n), None),
+        }
+    }
+
+    /// Selects the match after (or before) the current selection, wrapping around at the end.
+    pub fn find(&self, text_view: &gtk::TextView, backwards: bool) {
+        let buffer = text_view.get_buffer().expect("Couldn't get buffer");
+        let matches = match self.find_matches(&buffer, false) {
+            Ok(matches) => matches,
+            Err(err) => return self.set_status("Invalid pattern", Some(&err)),
+        };
+        if matches.is_empty() {
+            return self.update(&buffer);
+        }
+
+        let (start, end) = match buffer.get_selection_bounds() {
+            Some((start, end)) => (start.get_offset(), end.get_offset()),
+            None => {
+                let cursor = buffer.get_iter_at_mark(&buffer.get_insert().expect("No cursor"));
+                (cursor.get_offset(), cursor.get_offset())
+            }
+        };
+        let is_selected = |m: &Match| m.start == start && m.end == end;
+
+        let index = if backwards {
+            matches
+                .iter()
+                .rposition(|m| m.end <= start && !is_selected(m))
+                .unwrap_or(matches.len() - 1)
+        } else {
+            matches
+                .iter()
+                .position(|m| m.start >= end && !is_selected(m))
+                .or_else(|| {
+                    matches
+                        .iter()
+                        .position(|m| m.start >= start && !is_selected(m))
+                })
+                .unwrap_or(0)
+        };
+
+        let found = &matches[index];
+        let mut match_start = buffer.get_iter_at_offset(found.start);
+        buffer.select_range(&match_start, &buffer.get_iter_at_offset(found.end));
+        text_view.scroll_to_iter(&mut match_start, 0.1, false, 0.0, 0.0);
+        self.set_status(&format!("{} of {}", index + 1, matches.len()), None);
+    }
+
+    /// Replaces the selected match, if the selection is one, and moves on to the next.
+    pub fn replace(&self, text_view: &gtk::TextView) {
+        let buffer = text_view.get_buffer().expect("Couldn't get buffer");
+        let matches = match self.find_matches(&buffer, true) {
+            Ok(matches) => matches,
+            Err(err) => return self.set_status("Invalid pattern", Some(&err)),
+        };
+
+        if let Some((start, end)) = buffer.get_selection_bounds() {
+            let selected = matches
+                .iter()
+                .find(|m| m.start == start.get_offset() && m.end == end.get_offset());
+            if let Some(m) = selected {
+                replace_match(&buffer, m);
+            }
+        }
+
+        self.find(text_view, false);
+    }
+
+    pub fn replace_all(&self, buffer: &gtk::TextBuffer) {
+        let matches = match self.find_matches(buffer, true) {
+            Ok(matches) => matches,
+            Err(err) => return self.set_status("Invalid pattern", Some(&err)),
+        };
+
+        // A single user action, so that undoing it brings back every match at once. Going from
+        // the end keeps the offsets of the matches yet to come valid.
+        buffer.begin_user_action();
+        for m in matches.iter().rev() {
+            replace_match(buffer, m);
+        }
+        buffer.end_user_action();
+
+        self.update(buffer);
+        self.set_status(&format!("Replaced {}", matches.len()), None);
+    }
+}
+
+fn replace_match(buffer: &gtk::TextBuffer, m: &Match) {
+    let mut start = buffer.get_iter_at_offset(m.start);
+    let mut end = buffer.get_iter_at_offset(m.end);
+
+    buffer.begin_user_action();
+    buffer.delete(&mut start, &mut end);
+    buffer.insert(
+        &mut start,
+        m.replacement.as_ref().map_or("", String::as_str),
+    );
+    buffer.end_user_action();
+}
@@ -0,0 +1,7 @@
+//! Pieces shared by more than one of the examples in `src/bin`.
+
+extern crate gio;
+extern crate glib;
+extern crate gtk;
+
+pub mod undo;
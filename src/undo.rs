@@ -0,0 +1,557 @@
+//! Undo and redo for a `TextView`, which GTK 3 doesn't come with.
+//!
+//! Edits are recorded from the buffer's "insert-text" and "delete-range" signals. Everything
+//! done within one user action (see `TextBuffer::begin_user_action`) is undone in one go, and
+//! typing or deleting one character after the other is merged into whole words. Changes made
+//! outside of a user action, like loading a file, can't be undone and start the history over.
+//!
+//! Nothing in here is specific to one example, so any of them with a `TextView` can use it:
+//!
+//! ```ignore
+//! extern crate gtk_rs_examples;
+//!
+//! use gtk_rs_examples::undo::UndoManager;
+//!
+//! let undo = UndoManager::new(&text_view);
+//! undo.add_actions(&window);
+//! application.set_accels_for_action("win.undo", &["<Primary>Z"]);
+//! application.set_accels_for_action("win.redo", &["<Primary><Shift>Z"]);
+//! ```
+
+use std::cell::{Cell, RefCell};
+use std::collections::VecDeque;
+use std::rc::Rc;
+
+use gio;
+use gio::prelude::*;
+use glib::clone;
+use gtk;
+use gtk::prelude::*;
+
+// How much text the history may hold on to before it starts forgetting the oldest edits.
+const MAX_HISTORY_BYTES: usize = 4 * 1024 * 1024;
+
+/// A single change, with its position in characters.
+#[derive(Debug, PartialEq)]
+enum Edit {
+    Insert { offset: i32, text: String },
+    Delete { offset: i32, text: String },
+}
+
+impl Edit {
+    fn len(&self) -> usize {
+        match *self {
+            Edit::Insert { ref text, .. } | Edit::Delete { ref text, .. } => text.len(),
+        }
+    }
+
+    /// Merges `next` into `self` if both are part of typing or deleting the same word.
+    fn merge(&mut self, next: &Edit) -> bool {
+        match (self, next) {
+            (
+                Edit::Insert { offset, text },
+                Edit::Insert {
+                    offset: next_offset,
+                    text: next_text,
+                },
+            ) if *next_offset == *offset + text.chars().count() as i32
+                && same_word(text.chars().last(), next_text) =>
+            {
+                text.push_str(next_text);
+                true
+            }
+            // Backspace.
+            (
+                Edit::Delete { offset, text },
+                Edit::Delete {
+                    offset: next_offset,
+                    text: next_text,
+                },
+            ) if *next_offset + 1 == *offset && same_word(text.chars().next(), next_text) => {
+                text.insert_str(0, next_text);
+                *offset = *next_offset;
+                true
+            }
+            // Delete.
+            (
+                Edit::Delete { offset, text },
+                Edit::Delete {
+                    offset: next_offset,
+                    text: next_text,
+                },
+            ) if *next_offset == *offset && same_word(text.chars().last(), next_text) => {
+                text.push_str(next_text);
+                true
+            }
+            _ => false,
+        }
+    }
+}
+
+/// Whether the single character in `next` carries on from `previous` rather than starting a
+/// new word. Spaces and punctuation stick to the word that follows them.
+fn same_word(previous: Option<char>, next: &str) -> bool {
+    let mut chars = next.chars();
+    match (previous, chars.next(), chars.next()) {
+        (Some(previous), Some(next), None) => {
+            next == '_'
+                || next.is_alphanumeric()
+                || !(previous == '_' || previous.is_alphanumeric())
+        }
+        _ => false,
+    }
+}
+
+/// Whatever happened during one user action.
+type Group = Vec<Edit>;
+
+fn group_len(group: &[Edit]) -> usize {
+    group.iter().map(Edit::len).sum()
+}
+
+/// The groups of edits that can be undone and redone, kept apart from the buffer they were
+/// made to.
+struct History {
+    undo_stack: VecDeque<Group>,
+    redo_stack: Vec<Group>,
+    /// Where the undo stack was when the buffer was last saved, if that can still be reached.
+    saved_depth: Option<usize>,
+    bytes: usize,
+}
+
+impl History {
+    fn new() -> Self {
+        History {
+            undo_stack: VecDeque::new(),
+            redo_stack: Vec::new(),
+            saved_depth: Some(0),
+            bytes: 0,
+        }
+    }
+
+    /// Forgets all edits, the buffer being `saved` or not now.
+    fn clear(&mut self, saved: bool) {
+        self.undo_stack.clear();
+        self.redo_stack.clear();
+        self.bytes = 0;
+        self.saved_depth = if saved { Some(0) } else { None };
+    }
+
+    fn push(&mut self, group: Group) {
+        if group.is_empty() {
+            return;
+        }
+
+        let depth = self.undo_stack.len();
+        // Whatever was undone can't be redone anymore, saved state included.
+        if self.saved_depth > Some(depth) {
+            self.saved_depth = None;
+        }
+        let redone: usize = self.redo_stack.iter().map(|g| group_len(g)).sum();
+        self.redo_stack.clear();
+        self.bytes = self.bytes - redone + group_len(&group);
+
+        let merged = match (self.undo_stack.back_mut(), &group[..]) {
+            // Merging into the saved state would make it impossible to get back to.
+            (Some(last), [next]) if last.len() == 1 && self.saved_depth != Some(depth) => {
+                last[0].merge(next)
+            }
+            _ => false,
+        };
+        if !merged {
+            self.undo_stack.push_back(group);
+        }
+
+        while self.bytes > MAX_HISTORY_BYTES && self.undo_stack.len() > 1 {
+            let oldest = self
+                .undo_stack
+                .pop_front()
+                .expect("Undo stack can't be empty");
+            self.bytes -= group_len(&oldest);
+            self.saved_depth = self.saved_depth.and_then(|saved| saved.checked_sub(1));
+        }
+    }
+
+    /// Takes the group to undo, which goes to `undone` once it is.
+    fn undo(&mut self) -> Option<Group> {
+        self.undo_stack.pop_back()
+    }
+
+    fn undone(&mut self, group: Group) {
+        self.redo_stack.push(group);
+    }
+
+    /// Takes the group to redo, which goes to `redone` once it is.
+    fn redo(&mut self) -> Option<Group> {
+        self.redo_stack.pop()
+    }
+
+    fn redone(&mut self, group: Group) {
+        self.undo_stack.push_back(group);
+    }
+
+    /// Remembers that the buffer got saved as it is now.
+    fn set_saved(&mut self) {
+        self.saved_depth = Some(self.undo_stack.len());
+    }
+
+    fn is_saved(&self) -> bool {
+        self.saved_depth == Some(self.undo_stack.len())
+    }
+}
+
+pub struct UndoManager {
+    text_view: gtk::TextView,
+    buffer: gtk::TextBuffer,
+    pub undo_action: gio::SimpleAction,
+    pub redo_action: gio::SimpleAction,
+    history: RefCell<History>,
+    /// The edits of the user action going on right now.
+    pending: RefCell<Option<Group>>,
+    /// Set while undoing or redoing, so those edits don't get recorded themselves.
+    applying: Cell<bool>,
+}
+
+impl UndoManager {
+    pub fn new(text_view: &gtk::TextView) -> Rc<Self> {
+        let buffer = text_view.get_buffer().expect("Couldn't get buffer");
+        let manager = Rc::new(UndoManager {
+            text_view: text_view.clone(),
+            buffer: buffer.clone(),
+            undo_action: gio::SimpleAction::new("undo", None),
+            redo_action: gio::SimpleAction::new("redo", None),
+            history: RefCell::new(History::new()),
+            pending: RefCell::new(None),
+            applying: Cell::new(false),
+        });
+
+        manager
+            .undo_action
+            .connect_activate(clone!(@weak manager => move |_, _| {
+                manager.undo();
+            }));
+        manager
+            .redo_action
+            .connect_activate(clone!(@weak manager => move |_, _| {
+                manager.redo();
+            }));
+
+        buffer.connect_begin_user_action(clone!(@weak manager => move |_| {
+            if !manager.applying.get() {
+                *manager.pending.borrow_mut() = Some(Group::new());
+            }
+        }));
+        buffer.connect_end_user_action(clone!(@weak manager => move |_| {
+            let group = manager.pending.borrow_mut().take();
+            if let Some(group) = group {
+                manager.history.borrow_mut().push(group);
+                manager.update_actions();
+            }
+        }));
+        // Both run before the buffer changes, so the deleted text is still there to keep.
+        buffer.connect_insert_text(clone!(@weak manager => move |_, location, text| {
+            manager.record(Edit::Insert {
+                offset: location.get_offset(),
+                text: text.to_owned(),
+            });
+        }));
+        buffer.connect_delete_range(clone!(@weak manager => move |buffer, start, end| {
+            let text = buffer
+                .get_slice(start, end, true)
+                .map(String::from)
+                .unwrap_or_default();
+            manager.record(Edit::Delete {
+                offset: start.get_offset(),
+                text,
+            });
+        }));
+        buffer.connect_modified_changed(clone!(@weak manager => move |buffer| {
+            if !buffer.get_modified() {
+                manager.history.borrow_mut().set_saved();
+            }
+        }));
+
+        manager.update_actions();
+        manager
+    }
+
+    /// Adds the "undo" and "redo" actions to `map`, usually the window.
+    pub fn add_actions<M: IsA<gio::ActionMap>>(&self, map: &M) {
+        map.add_action(&self.undo_action);
+        map.add_action(&self.redo_action);
+    }
+
+    /// Forgets all edits made so far.
+    pub fn clear(&self) {
+        self.history.borrow_mut().clear(!self.buffer.get_modified());
+        self.update_actions();
+    }
+
+    fn record(&self, edit: Edit) {
+        if self.applying.get() {
+            return;
+        }
+
+        match *self.pending.borrow_mut() {
+            Some(ref mut group) => group.push(edit),
+            // The edits recorded so far may no longer fit what the buffer holds.
+            None => self.clear(),
+        }
+    }
+
+    pub fn undo(&self) {
+        let group = match self.history.borrow_mut().undo() {
+            Some(group) => group,
+            None => return,
+        };
+
+        for edit in group.iter().rev() {
+            match *edit {
+                Edit::Insert { offset, ref text } => {
+                    self.delete(offset, text.chars().count() as i32)
+                }
+                Edit::Delete { offset, ref text } => self.insert(offset, text),
+            }
+        }
+
+        self.history.borrow_mut().undone(group);
+        self.applied();
+    }
+
+    pub fn redo(&self) {
+        let group = match self.history.borrow_mut().redo() {
+            Some(group) => group,
+            None => return,
+        };
+
+        for edit in &group {
+            match *edit {
+                Edit::Insert { offset, ref text } => self.insert(offset, text),
+                Edit::Delete { offset, ref text } => {
+                    self.delete(offset, text.chars().count() as i32)
+                }
+            }
+        }
+
+        self.history.borrow_mut().redone(group);
+        self.applied();
+    }
+
+    fn insert(&self, offset: i32, text: &str) {
+        self.applying.set(true);
+        let mut iter = self.buffer.get_iter_at_offset(offset);
+        self.buffer.insert(&mut iter, text);
+        self.buffer.place_cursor(&iter);
+        self.applying.set(false);
+    }
+
+    fn delete(&self, offset: i32, chars: i32) {
+        self.applying.set(true);
+        let mut start = self.buffer.get_iter_at_offset(offset);
+        let mut end = self.buffer.get_iter_at_offset(offset + chars);
+        self.buffer.delete(&mut start, &mut end);
+        self.buffer.place_cursor(&start);
+        self.applying.set(false);
+    }
+
+    fn applied(&self) {
+        let saved = self.history.borrow().is_saved();
+        self.buffer.set_modified(!saved);
+
+        let insert = self.buffer.get_insert().expect("Couldn't get cursor");
+        self.text_view.scroll_mark_onscreen(&insert);
+        self.update_actions();
+    }
+
+    fn update_actions(&self) {
+        let history = self.history.borrow();
+        self.undo_action.set_enabled(!history.undo_stack.is_empty());
+        self.redo_action.set_enabled(!history.redo_stack.is_empty());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn insert(offset: i32, text: &str) -> Group {
+        vec![Edit::Insert {
+            offset,
+            text: text.to_owned(),
+        }]
+    }
+
+    fn delete(offset: i32, text: &str) -> Group {
+        vec![Edit::Delete {
+            offset,
+            text: text.to_owned(),
+        }]
+    }
+
+    /// Pushes the characters of `text` one by one, as if they were typed at `offset`.
+    fn type_text(history: &mut History, offset: i32, text: &str) {
+        for (i, c) in text.chars().enumerate() {
+            history.push(insert(offset + i as i32, &c.to_string()));
+        }
+    }
+
+    #[test]
+    fn same_word_carries_on_words() {
+        assert!(same_word(Some('a'), "b"));
+        assert!(same_word(Some('a'), "_"));
+        assert!(same_word(Some('_'), "1"));
+        assert!(!same_word(Some('a'), " "));
+        assert!(!same_word(Some('a'), "."));
+    }
+
+    #[test]
+    fn same_word_sticks_spaces_to_the_next_word() {
+        assert!(same_word(Some(' '), "a"));
+        assert!(same_word(Some(' '), " "));
+        assert!(same_word(Some('.'), ","));
+    }
+
+    #[test]
+    fn same_word_needs_a_single_character() {
+        assert!(!same_word(None, "a"));
+        assert!(!same_word(Some('a'), ""));
+        assert!(!same_word(Some('a'), "bc"));
+    }
+
+    #[test]
+    fn typing_is_merged_into_words() {
+        let mut history = History::new();
+        history.push(insert(0, "x"));
+        // Keeps what's typed next from being merged into the "x"
+        history.set_saved();
+        type_text(&mut history, 1, "foo bar");
+
+        let groups = history
+            .undo_stack
+            .iter()
+            .map(|group| group.as_slice())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            groups,
+            vec![
+                &insert(0, "x")[..],
+                &insert(1, "foo")[..],
+                &insert(4, " bar")[..]
+            ]
+        );
+    }
+
+    #[test]
+    fn backspaces_are_merged() {
+        let mut history = History::new();
+        history.push(insert(0, "abcd"));
+        history.push(delete(3, "d"));
+        history.push(delete(2, "c"));
+
+        assert_eq!(history.undo_stack.len(), 2);
+        assert_eq!(history.undo_stack[1], delete(2, "cd"));
+    }
+
+    #[test]
+    fn deletes_are_merged() {
+        let mut history = History::new();
+        history.push(insert(0, "abcd"));
+        history.push(delete(1, "b"));
+        history.push(delete(1, "c"));
+
+        assert_eq!(history.undo_stack.len(), 2);
+        assert_eq!(history.undo_stack[1], delete(1, "bc"));
+    }
+
+    #[test]
+    fn history_is_capped() {
+        let chunk = "a".repeat(MAX_HISTORY_BYTES / 3);
+        let mut history = History::new();
+        for i in 0..5 {
+            history.push(insert(i * chunk.len() as i32, &chunk));
+        }
+
+        assert_eq!(history.undo_stack.len(), 3);
+        assert_eq!(history.bytes, 3 * chunk.len());
+        // The oldest edits are the ones forgotten
+        assert_eq!(
+            history.undo_stack[0],
+            insert(2 * chunk.len() as i32, &chunk)
+        );
+    }
+
+    #[test]
+    fn last_edit_is_kept_even_if_too_big() {
+        let mut history = History::new();
+        history.push(insert(0, "x"));
+        history.push(insert(1, &"a".repeat(MAX_HISTORY_BYTES + 1)));
+
+        assert_eq!(history.undo_stack.len(), 1);
+        assert_eq!(history.bytes, MAX_HISTORY_BYTES + 1);
+    }
+
+    #[test]
+    fn undoing_and_redoing_returns_to_the_saved_state() {
+        let mut history = History::new();
+        history.push(insert(0, "a"));
+        history.set_saved();
+        history.push(insert(1, "b"));
+        assert!(!history.is_saved());
+
+        let group = history.undo().expect("Nothing to undo");
+        history.undone(group);
+        assert!(history.is_saved());
+
+        let group = history.undo().expect("Nothing to undo");
+        history.undone(group);
+        assert!(!history.is_saved());
+
+        for _ in 0..2 {
+            let group = history.redo().expect("Nothing to redo");
+            history.redone(group);
+        }
+        assert!(!history.is_saved());
+        let group = history.undo().expect("Nothing to undo");
+        history.undone(group);
+        assert!(history.is_saved());
+    }
+
+    #[test]
+    fn edits_are_not_merged_into_the_saved_state() {
+        let mut history = History::new();
+        history.push(insert(0, "a"));
+        history.set_saved();
+        history.push(insert(1, "b"));
+
+        assert_eq!(history.undo_stack.len(), 2);
+    }
+
+    #[test]
+    fn saved_state_is_lost_once_undone_edits_are_replaced() {
+        let mut history = History::new();
+        history.push(insert(0, "a"));
+        history.push(insert(1, " "));
+        history.set_saved();
+
+        let group = history.undo().expect("Nothing to undo");
+        history.undone(group);
+        history.push(insert(1, "."));
+
+        assert_eq!(history.saved_depth, None);
+        assert!(!history.is_saved());
+    }
+
+    #[test]
+    fn saved_state_is_lost_once_forgotten() {
+        let chunk = "a".repeat(MAX_HISTORY_BYTES / 2);
+        let mut history = History::new();
+        history.push(insert(0, &chunk));
+        history.set_saved();
+        history.push(insert(0, &chunk));
+        history.push(insert(0, &chunk));
+
+        assert_eq!(history.saved_depth, Some(0));
+        history.push(insert(0, &chunk));
+        assert_eq!(history.saved_depth, None);
+    }
+}